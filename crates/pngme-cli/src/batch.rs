@@ -0,0 +1,227 @@
+//! Expands path arguments into files and runs a command over each of them in parallel
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use serde_json::{json, Value};
+
+use crate::streams::is_stdio;
+use crate::Format;
+
+/// The result of running a command on one file
+pub struct Report {
+    /// Human readable result, may be empty
+    pub text: String,
+    /// Machine readable result
+    pub json: Value,
+    /// False if the command ran but found a problem with the file
+    pub ok: bool,
+}
+
+impl Report {
+    /// A successful report
+    pub fn new(text: String, json: Value) -> Self {
+        Report {
+            text,
+            json,
+            ok: true,
+        }
+    }
+}
+
+/// The files a list of path arguments refers to
+pub struct Targets {
+    /// Files to process, in argument order
+    pub files: Vec<PathBuf>,
+    /// Arguments that could not be expanded, with the reason
+    pub errors: Vec<(PathBuf, String)>,
+    /// True unless a single file path was given
+    pub is_batch: bool,
+}
+
+/// Expands globs and, when `recursive` is set, directories into the PNG files they contain
+pub fn expand(paths: &[PathBuf], recursive: bool) -> Targets {
+    let mut targets = Targets {
+        files: vec![],
+        errors: vec![],
+        is_batch: paths.len() > 1,
+    };
+    for path in paths {
+        let pattern = path.to_string_lossy();
+        if is_stdio(path) {
+            targets.files.push(path.clone());
+        } else if pattern.contains(['*', '?', '[']) {
+            targets.is_batch = true;
+            match glob::glob(&pattern) {
+                Ok(matches) => {
+                    let before = targets.files.len();
+                    for entry in matches {
+                        match entry {
+                            Ok(file) if file.is_dir() => {
+                                collect_dir(&file, recursive, &mut targets)
+                            }
+                            Ok(file) => targets.files.push(file),
+                            Err(e) => targets.errors.push((e.path().into(), e.to_string())),
+                        }
+                    }
+                    if targets.files.len() == before {
+                        targets
+                            .errors
+                            .push((path.clone(), "pattern matched no files".to_string()));
+                    }
+                }
+                Err(e) => targets.errors.push((path.clone(), e.to_string())),
+            }
+        } else if path.is_dir() {
+            targets.is_batch = true;
+            collect_dir(path, recursive, &mut targets);
+        } else {
+            targets.files.push(path.clone());
+        }
+    }
+    targets
+}
+
+fn collect_dir(dir: &Path, recursive: bool, targets: &mut Targets) {
+    if !recursive {
+        targets.errors.push((
+            dir.into(),
+            "is a directory, pass --recursive to process the PNG files in it".to_string(),
+        ));
+        return;
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            targets.errors.push((dir.into(), e.to_string()));
+            return;
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect_dir(&path, recursive, targets);
+        } else if is_png(&path) {
+            targets.files.push(path);
+        }
+    }
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+/// Runs `command` on every target in parallel, prints the per-file results followed by a
+/// summary and returns true if every file succeeded
+pub fn run<E, F>(targets: Targets, format: Format, command: F) -> bool
+where
+    E: std::fmt::Display,
+    F: Fn(&Path) -> Result<Report, E> + Sync,
+{
+    let outcomes: Vec<(PathBuf, Result<Report, String>)> = targets
+        .files
+        .into_par_iter()
+        .map(|path| {
+            let result = command(&path).map_err(|e| e.to_string());
+            (path, result)
+        })
+        .collect();
+    let failures = targets.errors.into_iter().map(|(path, e)| (path, Err(e)));
+    let outcomes: Vec<_> = outcomes.into_iter().chain(failures).collect();
+
+    let total = outcomes.len();
+    let failed = outcomes
+        .iter()
+        .filter(|(_, result)| !matches!(result, Ok(report) if report.ok))
+        .count();
+
+    match format {
+        Format::Text => {
+            for (path, result) in &outcomes {
+                match result {
+                    Ok(report) if report.text.is_empty() => println!("{}: ok", path.display()),
+                    Ok(report) => {
+                        println!("{}:", path.display());
+                        for line in report.text.lines() {
+                            println!("  {}", line);
+                        }
+                    }
+                    Err(e) => println!("{}: error: {}", path.display(), e),
+                }
+            }
+            eprintln!(
+                "Processed {} files: {} succeeded, {} failed",
+                total,
+                total - failed,
+                failed
+            );
+        }
+        Format::Json => {
+            let results: Vec<Value> = outcomes
+                .into_iter()
+                .map(|(path, result)| match result {
+                    Ok(report) => json!({ "path": path, "ok": report.ok, "result": report.json }),
+                    Err(e) => json!({ "path": path, "ok": false, "error": e }),
+                })
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({
+                    "results": results,
+                    "succeeded": total - failed,
+                    "failed": failed,
+                }))
+                .unwrap()
+            );
+        }
+    }
+    failed == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_tree() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("nested")).unwrap();
+        for file in ["a.png", "b.PNG", "notes.txt", "nested/c.png"] {
+            fs::write(root.path().join(file), b"").unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_expand_single_file() {
+        let targets = expand(&[PathBuf::from("dice.png")], false);
+        assert!(!targets.is_batch);
+        assert_eq!(targets.files, vec![PathBuf::from("dice.png")]);
+    }
+
+    #[test]
+    fn test_expand_directory() {
+        let temp = temp_tree();
+        let root = temp.path().to_path_buf();
+
+        let targets = expand(std::slice::from_ref(&root), false);
+        assert!(targets.files.is_empty());
+        assert_eq!(targets.errors.len(), 1);
+
+        let targets = expand(std::slice::from_ref(&root), true);
+        assert!(targets.is_batch);
+        assert_eq!(
+            targets.files,
+            vec![
+                root.join("a.png"),
+                root.join("b.PNG"),
+                root.join("nested/c.png")
+            ]
+        );
+
+        let pattern = root.join("*.png");
+        let targets = expand(&[pattern], false);
+        assert_eq!(targets.files, vec![root.join("a.png")]);
+    }
+}
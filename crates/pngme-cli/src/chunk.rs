@@ -0,0 +1,239 @@
+//! The `chunk` subcommands, which work on a single chunk picked by type or index
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::Subcommand;
+use serde_json::json;
+
+use pngme_lib::{hexdump, Chunk, ChunkType, Error, Png};
+
+use crate::batch::Report;
+use crate::diagnostic::{Diagnostic, InFile};
+use crate::output::WriteOptions;
+use crate::streams::{is_stdio, load_png, read_payload, STDIO};
+use crate::{conflict, emit, Format};
+
+#[derive(Debug, Subcommand)]
+pub enum ChunkCommand {
+    /// Write a chunk, serialized with its length, type and CRC, to a file
+    #[command(arg_required_else_help = true)]
+    Export {
+        path: PathBuf,
+        /// Chunk type, which picks the first chunk of that type, or index
+        chunk: Selector,
+        /// File to write to, `-` for stdout
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Write only the chunk data
+        #[arg(long)]
+        data_only: bool,
+    },
+    /// Insert a chunk written by `chunk export` into a PNG file
+    #[command(arg_required_else_help = true)]
+    Import {
+        path: PathBuf,
+        /// Serialized chunk, `-` for stdin
+        file: PathBuf,
+        /// Index to insert the chunk at [default: before IEND]
+        #[arg(long)]
+        at: Option<usize>,
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Print a hex dump of the data of a chunk
+    #[command(arg_required_else_help = true)]
+    Hexdump {
+        path: PathBuf,
+        /// Chunk type, which picks the first chunk of that type, or index
+        chunk: Selector,
+    },
+}
+
+/// Picks a chunk by its index or, for the first chunk of a type, by its type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    Index(usize),
+    Type(ChunkType),
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(index) = s.parse() {
+            return Ok(Selector::Index(index));
+        }
+        ChunkType::from_str(s)
+            .map(Selector::Type)
+            .map_err(|e| format!("expected a chunk index or type: {}", e))
+    }
+}
+
+impl Selector {
+    /// Finds the selected chunk in `png` along with its index
+    fn find<'a>(&self, png: &'a Png) -> Result<(usize, &'a Chunk), Error> {
+        let index = match self {
+            Selector::Index(index) => Some(*index),
+            Selector::Type(chunk_type) => png.position_of(&chunk_type.to_string()),
+        };
+        match (self, index.and_then(|i| png.chunk_at(i).map(|c| (i, c)))) {
+            (_, Some(found)) => Ok(found),
+            (Selector::Index(index), None) => Err(Error::ChunkIndexOutOfRange {
+                index: *index,
+                count: png.chunks().len(),
+            }),
+            (Selector::Type(chunk_type), None) => Err(Error::ChunkNotFound {
+                chunk_type: chunk_type.to_string(),
+            }),
+        }
+    }
+}
+
+/// Lowercase hex without separators, for JSON output
+fn hex_string(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn run(command: ChunkCommand, format: Format) -> Result<bool, Diagnostic> {
+    match command {
+        ChunkCommand::Export {
+            path,
+            chunk,
+            output,
+            data_only,
+        } => {
+            let png = load_png(&path).in_file(&path)?;
+            let (index, chunk) = chunk.find(&png).in_file(&path)?;
+            let bytes = if data_only {
+                chunk.data().to_vec()
+            } else {
+                chunk.as_bytes()
+            };
+            if is_stdio(&output) {
+                let mut stdout = io::stdout().lock();
+                stdout
+                    .write_all(&bytes)
+                    .and_then(|()| stdout.flush())
+                    .map_err(|source| Error::PNGWrite { source })?;
+            } else {
+                fs::write(&output, &bytes).map_err(|source| Error::PNGWrite { source })?;
+            }
+            emit(
+                format,
+                is_stdio(&output),
+                format!(
+                    "Exported {} #{} ({} bytes) to {}\n",
+                    chunk.chunk_type(),
+                    index,
+                    bytes.len(),
+                    output.display()
+                ),
+                json!({ "index": index, "chunk": chunk, "output": output, "bytes": bytes.len() }),
+            );
+        }
+        ChunkCommand::Import {
+            path,
+            file,
+            at,
+            write,
+        } => {
+            if is_stdio(&path) && is_stdio(&file) {
+                conflict("the PNG and the chunk cannot both be read from stdin");
+            }
+            let bytes = if is_stdio(&file) {
+                read_payload(STDIO)?
+            } else {
+                fs::read(&file).map_err(|source| Error::FileNotFound {
+                    source,
+                    path: file.clone(),
+                })?
+            };
+            let chunk = Chunk::try_from(bytes.as_slice())
+                .map_err(|source| Error::InvalidChunk { source })?;
+            let mut png = load_png(&path).in_file(&path)?;
+            let count = png.chunks().len();
+            let index = match at {
+                Some(index) if index > count => {
+                    return Err(Error::ChunkIndexOutOfRange { index, count }.into())
+                }
+                Some(index) => index,
+                None => png.position_of("IEND").unwrap_or(count),
+            };
+            let text = format!(
+                "Imported {} ({} bytes) at index {}\n",
+                chunk.chunk_type(),
+                chunk.length(),
+                index
+            );
+            let mut report = Report::new(text, json!({ "index": index, "chunk": chunk }));
+            png.insert_chunk(index, chunk);
+            let destination = write.write(&path, false, &png)?;
+            write.annotate(&mut report, &destination);
+            emit(format, write.writes_stdout(&path), report.text, report.json);
+        }
+        ChunkCommand::Hexdump { path, chunk } => {
+            let png = load_png(&path).in_file(&path)?;
+            let (index, chunk) = chunk.find(&png).in_file(&path)?;
+            let offset = png.chunk_offsets()[index];
+            emit(
+                format,
+                false,
+                format!(
+                    "{} #{} at byte {}, {} bytes, CRC {:#010x}\n{}",
+                    chunk.chunk_type(),
+                    index,
+                    offset,
+                    chunk.length(),
+                    chunk.crc(),
+                    hexdump(chunk.data())
+                ),
+                json!({
+                    "index": index,
+                    "offset": offset,
+                    "chunk": chunk,
+                    "data": hex_string(chunk.data()),
+                }),
+            );
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector() {
+        assert_eq!("3".parse(), Ok(Selector::Index(3)));
+        assert_eq!(
+            "tEXt".parse(),
+            Ok(Selector::Type(ChunkType::from_str("tEXt").unwrap()))
+        );
+        assert!("t3xt".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn test_selector_find() {
+        let png = Png::from_chunks(
+            ["IHDR", "tEXt", "tEXt", "IEND"]
+                .iter()
+                .map(|t| Chunk::new(ChunkType::from_str(t).unwrap(), vec![]))
+                .collect(),
+        );
+        let (index, _) = "tEXt".parse::<Selector>().unwrap().find(&png).unwrap();
+        assert_eq!(index, 1);
+        let (_, chunk) = Selector::Index(3).find(&png).unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "IEND");
+        assert!(matches!(
+            Selector::Index(4).find(&png),
+            Err(Error::ChunkIndexOutOfRange { index: 4, count: 4 })
+        ));
+        assert!(matches!(
+            "ruSt".parse::<Selector>().unwrap().find(&png),
+            Err(Error::ChunkNotFound { .. })
+        ));
+    }
+}
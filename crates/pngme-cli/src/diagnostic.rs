@@ -0,0 +1,281 @@
+//! Turns library errors into messages for people: the file involved, where in it the
+//! problem is, a hint when the mistake is a common one and a distinct exit code
+use std::error::Error as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use pngme_lib::{validate_bytes, ChunkType, Error, Problem, ValidationIssue};
+
+use crate::streams::is_stdio;
+
+/// The exit codes listed in `--help`, kept next to [`exit_code`] so they stay in sync
+pub const EXIT_CODES: &str = "\
+Exit codes:
+  0   success
+  1   a file in a batch failed, or validation found problems
+  2   invalid command line arguments
+  3   file not found or could not be opened
+  4   input could not be read
+  5   output could not be written
+  6   input is not a valid PNG file
+  7   invalid serialized chunk
+  8   invalid chunk type
+  9   chunk type not found
+  10  chunk index out of range
+  11  chunk data is not UTF-8 text
+  12  invalid dump text
+  13  invalid manifest
+  14  manifest row cannot be embedded
+  15  image data could not be decoded";
+
+/// The exit code for `error`, as listed in [`EXIT_CODES`]
+pub fn exit_code(error: &Error) -> u8 {
+    match error {
+        Error::FileNotFound { .. } => 3,
+        Error::Read { .. } => 4,
+        Error::PNGWrite { .. } => 5,
+        Error::PNGParse => 6,
+        Error::InvalidChunk { .. } => 7,
+        Error::InvalidChunkType { .. } => 8,
+        Error::ChunkNotFound { .. } => 9,
+        Error::ChunkIndexOutOfRange { .. } => 10,
+        Error::StrConversion => 11,
+        Error::DumpParse { .. } => 12,
+        Error::ManifestParse { .. } => 13,
+        Error::InvalidRow { .. } => 14,
+        Error::PixelDecode { .. } => 15,
+    }
+}
+
+/// An error along with the file it happened in and, for files that could not be parsed,
+/// the first problem found in them
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub error: Error,
+    pub path: Option<PathBuf>,
+    pub issue: Option<ValidationIssue>,
+}
+
+impl From<Error> for Diagnostic {
+    fn from(error: Error) -> Self {
+        Diagnostic {
+            error,
+            path: None,
+            issue: None,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Records that the error happened while working on `path`. A file that could not be
+    /// parsed is validated to find the byte offset and chunk of the problem. Only problems
+    /// that stop the parser count, since it reads chunks with a bad CRC without complaint.
+    pub fn in_file(mut self, path: &Path) -> Self {
+        if self.path.is_some() {
+            return self;
+        }
+        if matches!(self.error, Error::PNGParse) && !is_stdio(path) {
+            self.issue = fs::read(path).ok().and_then(|bytes| {
+                validate_bytes(&bytes).into_iter().find(|issue| {
+                    matches!(
+                        issue.problem,
+                        Problem::InvalidSignature
+                            | Problem::Truncated
+                            | Problem::InvalidChunkType { .. }
+                    )
+                })
+            });
+        }
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    /// The exit code for the error, as listed in [`EXIT_CODES`]
+    pub fn exit_code(&self) -> u8 {
+        exit_code(&self.error)
+    }
+
+    /// What went wrong, without the file name
+    pub fn message(&self) -> String {
+        let mut message = match &self.error {
+            Error::FileNotFound { path, source } if self.path.as_ref() == Some(path) => {
+                format!("cannot open: {}", source)
+            }
+            Error::FileNotFound { path, source } => {
+                format!("cannot open {}: {}", path.display(), source)
+            }
+            Error::Read { source } => format!("cannot read input: {}", source),
+            Error::PNGWrite { source } => format!("cannot write output: {}", source),
+            Error::PNGParse => "not a valid PNG file".to_string(),
+            Error::InvalidChunkType { chunk_type, source } => {
+                format!("`{}` is not a valid chunk type: {}", chunk_type, source)
+            }
+            Error::ChunkNotFound { chunk_type } => format!("no `{}` chunk found", chunk_type),
+            Error::StrConversion => "chunk data is not valid UTF-8 text".to_string(),
+            error => error.to_string(),
+        };
+        if let Some(issue) = &self.issue {
+            message += &format!(" ({})", issue);
+        }
+        message
+    }
+
+    /// A suggestion for fixing common mistakes
+    pub fn hint(&self) -> Option<String> {
+        let path = match &self.path {
+            Some(path) => path.display().to_string(),
+            None => "FILE".to_string(),
+        };
+        match &self.error {
+            Error::InvalidChunkType { chunk_type, .. } => {
+                Some(chunk_type_hint(chunk_type).unwrap_or_else(|| {
+                    "chunk types are four ASCII letters with an uppercase third letter, \
+                     such as `ruSt`"
+                        .to_string()
+                }))
+            }
+            Error::ChunkNotFound { chunk_type } => Some(
+                chunk_type_hint(chunk_type)
+                    .unwrap_or_else(|| format!("run `pngme print {}` to list its chunks", path)),
+            ),
+            Error::ChunkIndexOutOfRange { .. } => Some(format!(
+                "chunk indexes start at 0, run `pngme print {}` to list its chunks",
+                path
+            )),
+            Error::PNGParse if self.path.is_some() => Some(format!(
+                "run `pngme validate {}` to list every problem",
+                path
+            )),
+            Error::StrConversion => {
+                Some("use `pngme chunk export --data-only` to get the raw bytes".to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// A single line for batch results, which already show the file name
+    pub fn summary(&self) -> String {
+        match self.hint() {
+            Some(hint) => format!("{} (hint: {})", self.message(), hint),
+            None => self.message(),
+        }
+    }
+
+    /// The full report printed when a command fails. `verbose` adds every underlying
+    /// error in the chain.
+    pub fn render(&self, verbose: bool) -> String {
+        let mut out = match &self.path {
+            Some(path) if !is_stdio(path) => {
+                format!("error: {}: {}\n", path.display(), self.message())
+            }
+            Some(_) => format!("error: stdin: {}\n", self.message()),
+            None => format!("error: {}\n", self.message()),
+        };
+        if let Some(hint) = self.hint() {
+            out += &format!("  hint: {}\n", hint);
+        }
+        if verbose {
+            let mut source = self.error.source();
+            while let Some(error) = source {
+                out += &format!("  caused by: {}\n", error);
+                source = error.source();
+            }
+            out += &format!("  debug: {:?}\n", self.error);
+        }
+        out
+    }
+}
+
+/// Adds the file being worked on to errors, as in [`Diagnostic::in_file`]
+pub trait InFile<T> {
+    fn in_file(self, path: &Path) -> Result<T, Diagnostic>;
+}
+
+impl<T> InFile<T> for Result<T, Error> {
+    fn in_file(self, path: &Path) -> Result<T, Diagnostic> {
+        self.map_err(|error| Diagnostic::from(error).in_file(path))
+    }
+}
+
+/// Parses a chunk type argument, keeping the hint in the error clap reports
+pub fn parse_chunk_type(chunk_type: &str) -> Result<ChunkType, String> {
+    ChunkType::from_str(chunk_type).map_err(|source| {
+        Diagnostic::from(Error::InvalidChunkType {
+            chunk_type: chunk_type.to_string(),
+            source,
+        })
+        .summary()
+    })
+}
+
+/// Suggests the uppercase third letter for chunk types like `rust`, which is the most
+/// common reason a chunk type is rejected
+fn chunk_type_hint(chunk_type: &str) -> Option<String> {
+    let bytes = chunk_type.as_bytes();
+    if bytes.len() != 4 || !bytes.iter().all(u8::is_ascii_alphabetic) {
+        return None;
+    }
+    if !bytes[2].is_ascii_lowercase() {
+        return None;
+    }
+    let mut fixed = bytes.to_vec();
+    fixed[2] = fixed[2].to_ascii_uppercase();
+    Some(format!(
+        "the third letter of a chunk type must be uppercase, did you mean `{}`?",
+        String::from_utf8_lossy(&fixed)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme_lib::{Chunk, ChunkType, Png};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_chunk_type_hint() {
+        let error = ChunkType::from_str("rust").unwrap_err();
+        let diagnostic = Diagnostic::from(Error::InvalidChunkType {
+            chunk_type: "rust".to_string(),
+            source: error,
+        });
+        assert!(diagnostic.hint().unwrap().contains("`ruSt`"));
+        assert_eq!(diagnostic.exit_code(), 8);
+        assert!(chunk_type_hint("ruSt").is_none());
+        assert!(chunk_type_hint("ru5t").is_none());
+    }
+
+    #[test]
+    fn test_parse_error_locates_problem() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("broken.png");
+        let chunk =
+            |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; 13]);
+        let mut bytes = Png::from_chunks(vec![chunk("IHDR"), chunk("ruSt")]).as_bytes();
+        // A bad CRC in the first chunk, which the parser accepts, and a second chunk
+        // cut short, which makes it fail
+        bytes[8 + 8 + 13] ^= 0xff;
+        bytes.truncate(bytes.len() - 6);
+        fs::write(&path, &bytes).unwrap();
+
+        let diagnostic = Diagnostic::from(Error::PNGParse).in_file(&path);
+        assert_eq!(diagnostic.issue.as_ref().unwrap().chunk_index, Some(1));
+        let text = diagnostic.render(false);
+        assert!(!text.contains("CRC mismatch"), "{}", text);
+        assert!(text.contains("chunk 1 at byte 33"), "{}", text);
+        assert!(text.contains("hint: run `pngme validate"), "{}", text);
+    }
+
+    #[test]
+    fn test_verbose_shows_source_chain() {
+        let diagnostic = Diagnostic::from(Error::InvalidChunkType {
+            chunk_type: "ru".to_string(),
+            source: ChunkType::from_str("ru").unwrap_err(),
+        });
+        assert!(!diagnostic.render(false).contains("caused by"));
+        assert!(diagnostic
+            .render(true)
+            .contains("caused by: chunk type must be 4 bytes long"));
+    }
+}
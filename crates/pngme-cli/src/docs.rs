@@ -0,0 +1,79 @@
+//! Shell completions and man pages, generated from the clap definitions so they always
+//! list the current subcommands and options
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clap::{Command, CommandFactory};
+use clap_complete::Shell;
+use clap_mangen::Man;
+
+use crate::Cli;
+
+/// Name completions are registered for when none is given: the name this executable was
+/// run as, which differs from the command name when installed as `pngme-cli`
+pub fn default_bin_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| Cli::command().get_name().to_string())
+}
+
+/// Writes the completion script for `shell` to `out`
+pub fn completions(shell: Shell, bin_name: &str, out: &mut dyn Write) {
+    clap_complete::generate(shell, &mut Cli::command(), bin_name, out);
+}
+
+/// Writes the man page of the top level command to `out`
+pub fn man_page(out: &mut dyn Write) -> io::Result<()> {
+    Man::new(Cli::command()).render(out)
+}
+
+/// Writes a man page for the top level command and one for every subcommand, named like
+/// `pngme-chunk-export.1`, into `dir`. Returns the files written.
+pub fn man_pages(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fn generate(cmd: Command, dir: &Path, written: &mut Vec<PathBuf>) -> io::Result<()> {
+        for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+            generate(sub.clone(), dir, written)?;
+        }
+        written.push(Man::new(cmd).generate_to(dir)?);
+        Ok(())
+    }
+
+    fs::create_dir_all(dir)?;
+    let mut cmd = Cli::command().disable_help_subcommand(true);
+    cmd.build();
+    let mut written = vec![];
+    generate(cmd, dir, &mut written)?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions_cover_every_subcommand() {
+        let mut script = vec![];
+        completions(Shell::Bash, "pngme", &mut script);
+        let script = String::from_utf8(script).unwrap();
+        for sub in Cli::command().get_subcommands() {
+            assert!(script.contains(sub.get_name()), "{}", sub.get_name());
+        }
+    }
+
+    #[test]
+    fn test_man_pages_for_nested_subcommands() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let written = man_pages(dir).unwrap();
+        for name in ["pngme.1", "pngme-encode.1", "pngme-chunk-export.1"] {
+            assert!(written.contains(&dir.join(name)), "{}", name);
+        }
+        let page = fs::read_to_string(dir.join("pngme-strip.1")).unwrap();
+        assert!(page.contains("dry\\-run"));
+    }
+}
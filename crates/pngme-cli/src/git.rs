@@ -0,0 +1,129 @@
+//! Configures git to diff PNG files through `pngme textconv`
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Name of the diff driver in `.gitattributes` and the git config
+pub const DRIVER: &str = "pngme";
+
+/// Adds a `<pattern> diff=pngme` line to `attributes` for every pattern that does not
+/// already use the driver. Returns the new contents and the lines that were added.
+pub fn add_attributes(attributes: &str, patterns: &[String]) -> (String, Vec<String>) {
+    let driver = format!("diff={}", DRIVER);
+    let mut contents = attributes.to_string();
+    let mut added = vec![];
+    for pattern in patterns {
+        let present = attributes.lines().any(|line| {
+            let mut fields = line.split_whitespace();
+            fields.next() == Some(pattern) && fields.any(|field| field == driver)
+        });
+        if !present {
+            if !contents.is_empty() && !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            let line = format!("{} {}", pattern, driver);
+            contents += &format!("{}\n", line);
+            added.push(line);
+        }
+    }
+    (contents, added)
+}
+
+/// Quotes `path` for the shell git runs the textconv command with
+pub fn shell_quote(path: &str) -> String {
+    let plain = path
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-+:=@".contains(c));
+    if plain && !path.is_empty() {
+        path.to_string()
+    } else {
+        format!("'{}'", path.replace('\'', r"'\''"))
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("could not run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Sets up the diff driver for the git repository containing `dir` and returns a line
+/// describing each change made
+pub fn setup(
+    dir: &Path,
+    patterns: &[String],
+    command: &str,
+    global: bool,
+) -> Result<Vec<String>, String> {
+    let root = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?);
+    let mut changes = vec![];
+
+    let path = root.join(".gitattributes");
+    // Anything but a missing file must stop us, or the user's attributes would be lost
+    let existing = match fs::read_to_string(&path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("could not read {}: {}", path.display(), e)),
+    };
+    let (contents, added) = add_attributes(&existing, patterns);
+    if added.is_empty() {
+        changes.push(format!("{} already up to date", path.display()));
+    } else {
+        fs::write(&path, contents)
+            .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        for line in added {
+            changes.push(format!("Added `{}` to {}", line, path.display()));
+        }
+    }
+
+    let key = format!("diff.{}.textconv", DRIVER);
+    let scope = if global { "--global" } else { "--local" };
+    git(&root, &["config", scope, &key, command])?;
+    changes.push(format!("Set {} {} to `{}`", scope, key, command));
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_attributes() {
+        let patterns = vec!["*.png".to_string(), "*.PNG".to_string()];
+        let (contents, added) = add_attributes("*.txt text", &patterns);
+        assert_eq!(contents, "*.txt text\n*.png diff=pngme\n*.PNG diff=pngme\n");
+        assert_eq!(added.len(), 2);
+
+        let (again, added) = add_attributes(&contents, &patterns);
+        assert_eq!(again, contents);
+        assert!(added.is_empty());
+    }
+
+    #[test]
+    fn test_setup_keeps_unreadable_attributes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        git(dir, &["init", "-q"]).unwrap();
+        let attributes = dir.join(".gitattributes");
+        fs::write(&attributes, b"*.bin -diff\n\xff\n").unwrap();
+
+        let err = setup(dir, &["*.png".to_string()], "pngme textconv", false).unwrap_err();
+        assert!(err.contains("could not read"), "{}", err);
+        assert_eq!(fs::read(&attributes).unwrap(), b"*.bin -diff\n\xff\n");
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/usr/bin/pngme"), "/usr/bin/pngme");
+        assert_eq!(shell_quote("/my tools/pngme"), "'/my tools/pngme'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
@@ -6,50 +6,922 @@
 //! Based on the [`pngme book`].
 //!
 //! [`pngme book`]: https://jrdngr.github.io/pngme_book/
-use std::path::PathBuf;
+mod batch;
+mod chunk;
+mod diagnostic;
+mod docs;
+mod git;
+mod output;
+mod streams;
+mod tui;
 
-use clap::{command, Parser, Subcommand};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
 
-use pngme_lib::{decode, encode, print_chunks, remove, Error};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+
+use batch::{Report, Targets};
+use diagnostic::{Diagnostic, InFile};
+use output::WriteOptions;
+use pngme_lib::{
+    copy_chunks, diff_pngs, dump_png, encode_message, encode_rows, find_message, parse_dump,
+    read_manifest, read_png_from, remove_message, summarize_png, validate_bytes, Chunk, ChunkType,
+    DataEncoding, Error, Png, RowStatus, StripPolicy, StripPreset, TransplantOptions, NO_MESSAGE,
+};
+use streams::{
+    create_png, is_stdio, load_bytes, load_png, payload_uses_stdin, read_payload, STDIO,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "pngme")]
 #[command(about = "Hide secret message in png files", long_about = None)]
+#[command(
+    after_help = "Use `-` in place of a path to read a PNG from stdin and write the result to stdout."
+)]
+#[command(after_long_help = diagnostic::EXIT_CODES)]
 struct Cli {
+    /// Output format for results written to stdout
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Show the underlying causes of errors
+    #[arg(short, long, global = true)]
+    verbose: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Human readable text
+    Text,
+    /// Pretty printed JSON
+    Json,
+}
+
+/// The PNG files a subcommand runs on. Several files are processed in parallel.
+#[derive(Debug, Args)]
+struct Inputs {
+    /// PNG files, glob patterns or directories
+    #[arg(required = true, num_args = 1..)]
+    paths: Vec<PathBuf>,
+    /// Process every PNG file inside directories, including subdirectories
+    #[arg(short, long)]
+    recursive: bool,
+}
+
+/// Where `encode` takes the message from, instead of the argument after the chunk type.
+/// At most one can be given.
+#[derive(Debug, Args)]
+#[group(multiple = false)]
+struct MessageSource {
+    /// The message, or `-` to read it from stdin or `@FILE` to read it from a file
+    #[arg(short, long, allow_hyphen_values = true)]
+    message: Option<String>,
+    /// Read the message from a file
+    #[arg(long, value_name = "FILE")]
+    message_file: Option<PathBuf>,
+    /// Read the message from stdin
+    #[arg(long)]
+    message_stdin: bool,
+    /// Ask for the message without echoing it to the terminal
+    #[arg(long)]
+    prompt: bool,
+}
+
+impl MessageSource {
+    /// Returns true if one of the message options was given
+    fn is_given(&self) -> bool {
+        self.message.is_some() || self.message_file.is_some() || self.message_stdin || self.prompt
+    }
+
+    /// Returns true if reading the message consumes stdin
+    fn uses_stdin(&self) -> bool {
+        self.message_stdin || self.message.as_deref().is_some_and(payload_uses_stdin)
+    }
+
+    /// Reads the message from whichever source was given
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        if let Some(message) = &self.message {
+            if !payload_uses_stdin(message) && !message.starts_with('@') {
+                eprintln!(
+                    "warning: messages given as arguments can be seen in shell history and \
+                     process listings, use --message-file, --message-stdin or --prompt instead"
+                );
+            }
+            read_payload(message)
+        } else if let Some(path) = &self.message_file {
+            fs::read(path).map_err(|source| Error::FileNotFound {
+                source,
+                path: path.clone(),
+            })
+        } else if self.message_stdin {
+            read_payload(STDIO)
+        } else {
+            rpassword::prompt_password("Message: ")
+                .map(String::into_bytes)
+                .map_err(|source| Error::Read { source })
+        }
+    }
+}
+
 #[derive(Debug, Subcommand)]
 enum Commands {
     #[command(arg_required_else_help = true)]
+    #[command(
+        override_usage = "pngme encode [OPTIONS] <PATH>... <CHUNK_TYPE> <MESSAGE>\n       pngme encode [OPTIONS] <--message <MESSAGE>|--message-file <FILE>|--message-stdin|--prompt> <PATH>... <CHUNK_TYPE>"
+    )]
     Encode {
+        /// PNG files, glob patterns or directories followed by the chunk type and, unless
+        /// one of the message options is used, the message. The message can be `-` to
+        /// read it from stdin or `@FILE` to read it from a file
+        #[arg(required = true, num_args = 2.., value_name = "ARGS")]
+        operands: Vec<String>,
+        /// Process every PNG file inside directories, including subdirectories
+        #[arg(short, long)]
+        recursive: bool,
+        #[command(flatten)]
+        message: MessageSource,
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    #[command(arg_required_else_help = true)]
+    Decode {
+        #[command(flatten)]
+        inputs: Inputs,
+        #[arg(value_parser = diagnostic::parse_chunk_type)]
+        chunk_type: ChunkType,
+    },
+    #[command(arg_required_else_help = true)]
+    Remove {
+        #[command(flatten)]
+        inputs: Inputs,
+        #[arg(value_parser = diagnostic::parse_chunk_type)]
+        chunk_type: ChunkType,
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    #[command(arg_required_else_help = true)]
+    Print {
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Remove metadata and hidden chunks, always keeping critical chunks
+    #[command(arg_required_else_help = true)]
+    Strip {
+        #[command(flatten)]
+        inputs: Inputs,
+        /// Ancillary chunk types to keep, all others are removed
+        #[arg(long, value_delimiter = ',', value_parser = ChunkType::from_str, conflicts_with_all = ["remove", "preset"])]
+        keep: Vec<ChunkType>,
+        /// Chunk types to remove, all others are kept
+        #[arg(long, value_delimiter = ',', value_parser = ChunkType::from_str, conflicts_with = "preset")]
+        remove: Vec<ChunkType>,
+        /// Named policy to apply when neither `--keep` nor `--remove` is given
+        #[arg(long, value_enum, default_value_t = Preset::Minimal)]
+        preset: Preset,
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Check the signature, chunk CRCs and required chunks of PNG files
+    #[command(arg_required_else_help = true)]
+    Validate {
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Copy ancillary chunks from one PNG file into another
+    #[command(arg_required_else_help = true)]
+    Transplant {
+        source: PathBuf,
+        target: PathBuf,
+        /// Chunk types to copy even if they are unsafe to copy
+        #[arg(long, value_delimiter = ',', value_parser = ChunkType::from_str)]
+        include: Vec<ChunkType>,
+        /// Chunk types to never copy
+        #[arg(long, value_delimiter = ',', value_parser = ChunkType::from_str)]
+        exclude: Vec<ChunkType>,
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Embed a message into every PNG file listed in a CSV or JSONL manifest
+    #[command(arg_required_else_help = true)]
+    #[command(
+        after_help = "Manifest columns: input, output, chunk_type, message or payload_file, key.\nRows already recorded as ok in the log are skipped, so re-running resumes an interrupted run."
+    )]
+    Bulk {
+        /// Manifest file, read as JSON lines if it ends in `.jsonl` and as CSV otherwise
+        manifest: PathBuf,
+        /// Result log to append to [default: the manifest path with `.log.jsonl` appended]
+        #[arg(long)]
+        log: Option<PathBuf>,
+    },
+    /// Write a PNG file as readable text that `build` turns back into the same file
+    #[command(arg_required_else_help = true)]
+    Dump {
         path: PathBuf,
-        chunk_type: String,
-        message: String,
+        /// Encoding for chunk data that is not text
+        #[arg(long, value_enum, default_value_t = Encoding::Hex)]
+        encoding: Encoding,
+        /// File to write the text to [default: stdout]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
+    /// Turn the text written by `dump` back into a PNG file
     #[command(arg_required_else_help = true)]
-    Decode { path: PathBuf, chunk_type: String },
+    Build {
+        /// Text written by `dump`, `-` for stdin
+        dump: PathBuf,
+        /// PNG file to create or replace, `-` for stdout
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Print a diff friendly summary of a PNG file, for use as a git textconv filter
+    #[command(arg_required_else_help = true)]
+    Textconv { path: PathBuf },
+    /// Configure the current git repository to diff PNG files with `textconv`
+    GitSetup {
+        /// Patterns to add to .gitattributes
+        #[arg(long = "pattern", default_values_t = ["*.png".to_string()])]
+        patterns: Vec<String>,
+        /// Command git runs to convert a PNG to text [default: this executable with `textconv`]
+        #[arg(long)]
+        command: Option<String>,
+        /// Configure the diff driver in the global git config instead of the repository's
+        #[arg(long)]
+        global: bool,
+    },
+    /// Export, import or hex dump a single chunk
+    Chunk {
+        #[command(subcommand)]
+        command: chunk::ChunkCommand,
+    },
+    /// Inspect and edit the chunks of a PNG file interactively
     #[command(arg_required_else_help = true)]
-    Remove { path: PathBuf, chunk_type: String },
+    Tui { path: PathBuf },
+    /// Show the chunk-level differences between two PNG files
     #[command(arg_required_else_help = true)]
-    Print { path: PathBuf },
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// Include a hex diff of the data of modified chunks
+        #[arg(long)]
+        hex: bool,
+    },
+    /// Print a shell completion script
+    #[command(arg_required_else_help = true)]
+    #[command(
+        after_help = "For example, add `source <(pngme completions bash)` to ~/.bashrc or run\n`pngme completions fish > ~/.config/fish/completions/pngme.fish`."
+    )]
+    Completions {
+        shell: clap_complete::Shell,
+        /// Command name to complete [default: the name this executable was run as]
+        #[arg(long)]
+        bin_name: Option<String>,
+    },
+    /// Print the man page, or write one for every subcommand into a directory
+    Man {
+        /// Directory to write `pngme.1` and a page per subcommand into
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Preset {
+    /// Keep only critical chunks
+    Minimal,
+    /// Keep critical chunks plus sRGB, gAMA, cHRM, iCCP and tRNS
+    Web,
+    /// Remove eXIf, tEXt, zTXt, iTXt and tIME chunks and private chunks such as ruSt
+    Metadata,
+}
+
+impl From<Preset> for StripPreset {
+    fn from(preset: Preset) -> Self {
+        match preset {
+            Preset::Minimal => StripPreset::Minimal,
+            Preset::Web => StripPreset::Web,
+            Preset::Metadata => StripPreset::Metadata,
+        }
+    }
+}
+
+/// Prints the result of a command. Results go to stderr when stdout carries PNG data.
+fn emit(format: Format, png_to_stdout: bool, text: String, json: Value) {
+    let output = match format {
+        Format::Text if text.is_empty() => return,
+        Format::Text => text,
+        Format::Json => format!("{}\n", serde_json::to_string_pretty(&json).unwrap()),
+    };
+    if png_to_stdout {
+        eprint!("{}", output);
+    } else {
+        print!("{}", output);
+    }
+}
+
+impl Commands {
+    /// The name of the subcommand if what it writes is not a result that `--format`
+    /// could change, such as a dump, a shell script or a man page
+    fn fixed_output(&self) -> Option<&'static str> {
+        match self {
+            Commands::Dump { .. } => Some("dump"),
+            Commands::Textconv { .. } => Some("textconv"),
+            Commands::Completions { .. } => Some("completions"),
+            Commands::Man { output: None } => Some("man"),
+            Commands::Tui { .. } => Some("tui"),
+            _ => None,
+        }
+    }
+}
+
+fn conflict(message: &str) -> ! {
+    Cli::command()
+        .error(ErrorKind::ArgumentConflict, message)
+        .exit()
+}
+
+/// How `dump` writes binary chunk data
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Encoding {
+    /// Lowercase hex
+    Hex,
+    /// Standard base64, about two thirds the size of hex
+    Base64,
+}
+
+impl From<Encoding> for DataEncoding {
+    fn from(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Hex => DataEncoding::Hex,
+            Encoding::Base64 => DataEncoding::Base64,
+        }
+    }
+}
+
+/// A usage error of `encode`, shown with its usage rather than that of `pngme`
+fn encode_error(kind: ErrorKind, message: impl std::fmt::Display) -> clap::Error {
+    let mut command = Cli::command();
+    command
+        .find_subcommand_mut("encode")
+        .expect("encode is a subcommand")
+        .error(kind, message)
 }
 
-fn main() -> Result<(), Error> {
+/// Splits the operands of `encode` into the paths and the chunk type. Without a message
+/// option the last operand is the message, which is moved into `message`.
+fn encode_operands(
+    mut operands: Vec<String>,
+    message: &mut MessageSource,
+) -> Result<(Vec<PathBuf>, ChunkType), clap::Error> {
+    let given = message.is_given();
+    if !given {
+        if operands.len() < 3 {
+            return Err(encode_error(
+                ErrorKind::MissingRequiredArgument,
+                "a message is required, either after the chunk type or with --message, \
+                 --message-file, --message-stdin or --prompt",
+            ));
+        }
+        message.message = operands.pop();
+    }
+    // clap requires at least two operands
+    let chunk_type = operands.pop().unwrap_or_default();
+    let chunk_type = diagnostic::parse_chunk_type(&chunk_type).map_err(|reason| {
+        let message_after_chunk_type = operands.len() > 1
+            && operands
+                .last()
+                .is_some_and(|last| diagnostic::parse_chunk_type(last).is_ok());
+        if given && message_after_chunk_type {
+            encode_error(
+                ErrorKind::ArgumentConflict,
+                "the message cannot be given both after the chunk type and with a message option",
+            )
+        } else {
+            encode_error(
+                ErrorKind::ValueValidation,
+                format!("invalid chunk type: {}", reason),
+            )
+        }
+    })?;
+    Ok((
+        operands.into_iter().map(PathBuf::from).collect(),
+        chunk_type,
+    ))
+}
+
+fn chunk_list(chunks: &[Chunk]) -> String {
+    chunks
+        .iter()
+        .map(|chunk| format!("  {} ({} bytes)\n", chunk.chunk_type(), chunk.length()))
+        .collect()
+}
+
+/// Runs `command` on a single file, or on every file in parallel when several paths,
+/// globs or directories are given. Returns false if any file failed.
+fn run_each<F>(
+    paths: &[PathBuf],
+    recursive: bool,
+    format: Format,
+    command: F,
+) -> Result<bool, Diagnostic>
+where
+    F: Fn(&Path) -> Result<Report, Error> + Sync,
+{
+    run_targets(batch::expand(paths, recursive), format, false, command)
+}
+
+/// Runs `edit` on every input like [`run_each`] and writes each edited PNG where `write`
+/// says, over the input by default
+fn run_edits<F>(
+    paths: &[PathBuf],
+    recursive: bool,
+    format: Format,
+    write: &WriteOptions,
+    edit: F,
+) -> Result<bool, Diagnostic>
+where
+    F: Fn(&Path) -> Result<(Report, Png), Error> + Sync,
+{
+    let targets = batch::expand(paths, recursive);
+    let into_dir = targets.is_batch || !targets.errors.is_empty();
+    let png_to_stdout = if into_dir {
+        if let Err(message) = write.prepare_batch(&targets.files) {
+            conflict(&message);
+        }
+        false
+    } else {
+        write.writes_stdout(&targets.files[0])
+    };
+    run_targets(targets, format, png_to_stdout, |path| {
+        let (mut report, png) = edit(path)?;
+        let destination = write.write(path, into_dir, &png)?;
+        write.annotate(&mut report, &destination);
+        Ok(report)
+    })
+}
+
+fn run_targets<F>(
+    targets: Targets,
+    format: Format,
+    png_to_stdout: bool,
+    command: F,
+) -> Result<bool, Diagnostic>
+where
+    F: Fn(&Path) -> Result<Report, Error> + Sync,
+{
+    if targets.is_batch || !targets.errors.is_empty() {
+        if targets.files.iter().any(|path| is_stdio(path)) {
+            conflict("`-` cannot be combined with other paths");
+        }
+        return Ok(batch::run(targets, format, |path| {
+            command(path).map_err(|e| Diagnostic::from(e).in_file(path).summary())
+        }));
+    }
+    let path = &targets.files[0];
+    let report = command(path).in_file(path)?;
+    emit(format, png_to_stdout, report.text, report.json);
+    Ok(report.ok)
+}
+
+fn encode_file(
+    path: &Path,
+    chunk_type: &ChunkType,
+    payload: &[u8],
+) -> Result<(Report, Png), Error> {
+    let mut png = load_png(path)?;
+    encode_message(&mut png, &chunk_type.to_string(), payload)?;
+    let report = Report::new(
+        String::new(),
+        json!({ "path": path, "chunk_type": chunk_type, "length": payload.len() }),
+    );
+    Ok((report, png))
+}
+
+fn decode_file(path: &Path, chunk_type: &ChunkType) -> Result<Report, Error> {
+    let message = find_message(&load_png(path)?, &chunk_type.to_string())?;
+    let text = format!("{}\n", message.as_deref().unwrap_or(NO_MESSAGE));
+    Ok(Report::new(
+        text,
+        json!({ "path": path, "chunk_type": chunk_type, "message": message }),
+    ))
+}
+
+fn remove_file(path: &Path, chunk_type: &ChunkType) -> Result<(Report, Png), Error> {
+    let mut png = load_png(path)?;
+    remove_message(&mut png, &chunk_type.to_string())?;
+    let report = Report::new(
+        String::new(),
+        json!({ "path": path, "chunk_type": chunk_type }),
+    );
+    Ok((report, png))
+}
+
+fn print_file(path: &Path) -> Result<Report, Error> {
+    let png = load_png(path)?;
+    let text = png
+        .chunks()
+        .iter()
+        .map(|chunk| format!("{}\n", chunk))
+        .collect();
+    Ok(Report::new(text, json!(png)))
+}
+
+fn strip_file(path: &Path, policy: &StripPolicy) -> Result<(Report, Png), Error> {
+    let mut png = load_png(path)?;
+    let removed = png.strip(policy);
+    let text = if removed.is_empty() {
+        "No chunks removed\n".to_string()
+    } else {
+        format!(
+            "Removed {} chunks:\n{}",
+            removed.len(),
+            chunk_list(&removed)
+        )
+    };
+    let report = Report::new(text, json!({ "path": path, "removed": removed }));
+    Ok((report, png))
+}
+
+fn validate_file(path: &Path) -> Result<Report, Error> {
+    let issues = validate_bytes(&load_bytes(path)?);
+    let text = if issues.is_empty() {
+        "valid\n".to_string()
+    } else {
+        issues.iter().map(|issue| format!("{}\n", issue)).collect()
+    };
+    Ok(Report {
+        text,
+        json: json!({ "path": path, "valid": issues.is_empty(), "issues": issues }),
+        ok: issues.is_empty(),
+    })
+}
+
+fn main() -> ExitCode {
     let args = Cli::parse();
-    match args.command {
+    let verbose = args.verbose;
+    match run(args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(diagnostic) => {
+            eprint!("{}", diagnostic.render(verbose));
+            ExitCode::from(diagnostic.exit_code())
+        }
+    }
+}
+
+/// Runs the parsed command. Returns false if it ran but some of its work failed.
+fn run(args: Cli) -> Result<bool, Diagnostic> {
+    let format = args.format;
+    if let (Format::Json, Some(name)) = (format, args.command.fixed_output()) {
+        conflict(&format!(
+            "`{}` has an output format of its own and cannot be used with --format json",
+            name
+        ));
+    }
+    let ok = match args.command {
         Commands::Encode {
-            path,
+            operands,
+            recursive,
+            mut message,
+            write,
+        } => {
+            let (paths, chunk_type) =
+                encode_operands(operands, &mut message).unwrap_or_else(|e| e.exit());
+            if message.uses_stdin() && paths.iter().any(|path| is_stdio(path)) {
+                conflict("the PNG and the message cannot both be read from stdin");
+            }
+            let payload = message.read()?;
+            run_edits(&paths, recursive, format, &write, |path| {
+                encode_file(path, &chunk_type, &payload)
+            })?
+        }
+        Commands::Decode { inputs, chunk_type } => {
+            run_each(&inputs.paths, inputs.recursive, format, |path| {
+                decode_file(path, &chunk_type)
+            })?
+        }
+        Commands::Remove {
+            inputs,
             chunk_type,
-            message,
-        } => encode(path, chunk_type, message)?,
-        Commands::Decode { path, chunk_type } => {
-            let msg = decode(path, chunk_type)?;
-            println!("{}", msg);
+            write,
+        } => run_edits(&inputs.paths, inputs.recursive, format, &write, |path| {
+            remove_file(path, &chunk_type)
+        })?,
+        Commands::Print { inputs } => {
+            run_each(&inputs.paths, inputs.recursive, format, print_file)?
+        }
+        Commands::Strip {
+            inputs,
+            keep,
+            remove,
+            preset,
+            write,
+        } => {
+            let policy = if !keep.is_empty() {
+                StripPolicy::Allow(keep)
+            } else if !remove.is_empty() {
+                StripPolicy::Deny(remove)
+            } else {
+                StripPolicy::from(StripPreset::from(preset))
+            };
+            run_edits(&inputs.paths, inputs.recursive, format, &write, |path| {
+                strip_file(path, &policy)
+            })?
+        }
+        Commands::Validate { inputs } => {
+            run_each(&inputs.paths, inputs.recursive, format, validate_file)?
+        }
+        Commands::Transplant {
+            source,
+            target,
+            include,
+            exclude,
+            write,
+        } => {
+            if is_stdio(&source) && is_stdio(&target) {
+                conflict("the source and target cannot both be read from stdin");
+            }
+            let source_png = load_png(&source).in_file(&source)?;
+            let mut target_png = load_png(&target).in_file(&target)?;
+            let report = copy_chunks(
+                &source_png,
+                &mut target_png,
+                &TransplantOptions { include, exclude },
+            );
+            let mut text = format!(
+                "Copied {} chunks:\n{}",
+                report.copied.len(),
+                chunk_list(&report.copied)
+            );
+            if !report.skipped.is_empty() {
+                text += &format!("Skipped {} chunks:\n", report.skipped.len());
+                for (chunk, reason) in &report.skipped {
+                    text += &format!("  {} ({})\n", chunk.chunk_type(), reason);
+                }
+            }
+            let skipped: Vec<_> = report
+                .skipped
+                .iter()
+                .map(|(chunk, reason)| json!({ "chunk": chunk, "reason": reason }))
+                .collect();
+            let mut report = Report::new(
+                text,
+                json!({
+                    "source": source,
+                    "target": target,
+                    "copied": report.copied,
+                    "skipped": skipped,
+                }),
+            );
+            let destination = write.write(&target, false, &target_png)?;
+            write.annotate(&mut report, &destination);
+            emit(
+                format,
+                write.writes_stdout(&target),
+                report.text,
+                report.json,
+            );
+            true
+        }
+        Commands::Bulk { manifest, log } => {
+            let log = log.unwrap_or_else(|| {
+                let mut log = manifest.clone().into_os_string();
+                log.push(".log.jsonl");
+                log.into()
+            });
+            let report = encode_rows(&read_manifest(&manifest)?, &log)?;
+            let mut text = String::new();
+            for result in &report.results {
+                text += &format!(
+                    "line {}: {} -> {}: ",
+                    result.row,
+                    result.input.display(),
+                    result.output.display()
+                );
+                text += &match (&result.status, &result.error) {
+                    (RowStatus::Failed, Some(error)) => format!("error: {}\n", error),
+                    (RowStatus::Skipped, _) => "skipped, already done\n".to_string(),
+                    _ => "ok\n".to_string(),
+                };
+            }
+            let failed = report.count(RowStatus::Failed);
+            emit(format, false, text, json!(report));
+            eprintln!(
+                "Processed {} rows: {} embedded, {} skipped, {} failed. Results logged to {}",
+                report.results.len(),
+                report.count(RowStatus::Ok),
+                report.count(RowStatus::Skipped),
+                failed,
+                log.display()
+            );
+            failed == 0
+        }
+        Commands::Dump {
+            path,
+            encoding,
+            output,
+        } => {
+            let text = dump_png(&load_png(&path).in_file(&path)?, encoding.into());
+            match output {
+                Some(output) if !is_stdio(&output) => {
+                    fs::write(&output, text).map_err(|source| Error::PNGWrite { source })?
+                }
+                _ => print!("{}", text),
+            }
+            true
+        }
+        Commands::Build { dump, output } => {
+            let text = String::from_utf8(load_bytes(&dump)?)
+                .map_err(|_| Error::StrConversion)
+                .in_file(&dump)?;
+            let png = parse_dump(&text).in_file(&dump)?;
+            create_png(&output, &png)?;
+            emit(
+                format,
+                is_stdio(&output),
+                format!(
+                    "Built {} with {} chunks\n",
+                    output.display(),
+                    png.chunks().len()
+                ),
+                json!({ "output": output, "chunks": png.chunks().len() }),
+            );
+            true
+        }
+        Commands::Textconv { path } => {
+            // Git shows whatever this prints, so an unreadable file is described rather
+            // than failing the whole diff
+            let bytes = load_bytes(&path)?;
+            match read_png_from(bytes.as_slice()) {
+                Ok(png) => print!("{}", summarize_png(&png)),
+                Err(_) => {
+                    println!("# not a valid PNG file ({} bytes)", bytes.len());
+                    for issue in validate_bytes(&bytes) {
+                        println!("# {}", issue);
+                    }
+                }
+            }
+            true
+        }
+        Commands::GitSetup {
+            patterns,
+            command,
+            global,
+        } => {
+            let command = match command {
+                Some(command) => command,
+                None => {
+                    let exe = std::env::current_exe().map_err(|source| Error::Read { source })?;
+                    format!("{} textconv", git::shell_quote(&exe.to_string_lossy()))
+                }
+            };
+            let dir = std::env::current_dir().map_err(|source| Error::Read { source })?;
+            match git::setup(&dir, &patterns, &command, global) {
+                Ok(changes) => {
+                    let text = changes.iter().map(|line| format!("{}\n", line)).collect();
+                    emit(format, false, text, json!({ "changes": changes }));
+                    true
+                }
+                Err(e) => {
+                    eprintln!("git-setup failed: {}", e);
+                    false
+                }
+            }
+        }
+        Commands::Chunk { command } => chunk::run(command, format)?,
+        Commands::Tui { path } => {
+            if is_stdio(&path) {
+                conflict("the interactive editor needs a file, not stdin");
+            }
+            match tui::run(tui::App::open(path.clone()).in_file(&path)?) {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("Terminal error: {}", e);
+                    false
+                }
+            }
+        }
+        Commands::Completions { shell, bin_name } => {
+            let bin_name = bin_name.unwrap_or_else(docs::default_bin_name);
+            docs::completions(shell, &bin_name, &mut std::io::stdout());
+            true
         }
-        Commands::Remove { path, chunk_type } => remove(path, chunk_type)?,
-        Commands::Print { path } => print_chunks(path)?,
+        Commands::Man { output } => {
+            match output {
+                Some(dir) => {
+                    let written =
+                        docs::man_pages(&dir).map_err(|source| Error::PNGWrite { source })?;
+                    let text = written
+                        .iter()
+                        .map(|path| format!("Wrote {}\n", path.display()))
+                        .collect();
+                    emit(format, false, text, json!({ "written": written }));
+                }
+                None => {
+                    docs::man_page(&mut std::io::stdout())
+                        .map_err(|source| Error::PNGWrite { source })?;
+                }
+            }
+            true
+        }
+        Commands::Diff { old, new, hex } => {
+            if is_stdio(&old) && is_stdio(&new) {
+                conflict("only one of the compared files can be read from stdin");
+            }
+            let changes = diff_pngs(
+                &load_png(&old).in_file(&old)?,
+                &load_png(&new).in_file(&new)?,
+                hex,
+            );
+            emit(format, false, changes.to_string(), json!(changes));
+            true
+        }
+    };
+    Ok(ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `encode` arguments and splits the operands
+    fn encode(args: &[&str]) -> Result<(Vec<PathBuf>, ChunkType, MessageSource), ErrorKind> {
+        let cli =
+            Cli::try_parse_from(["pngme", "encode"].iter().chain(args)).map_err(|e| e.kind())?;
+        let Commands::Encode {
+            operands,
+            mut message,
+            ..
+        } = cli.command
+        else {
+            panic!("expected encode");
+        };
+        let (paths, chunk_type) = encode_operands(operands, &mut message).map_err(|e| e.kind())?;
+        Ok((paths, chunk_type, message))
+    }
+
+    #[test]
+    fn test_encode_arguments() {
+        let (paths, chunk_type, message) = encode(&["dice.png", "ruSt", "hello"]).unwrap();
+        assert_eq!(paths, [PathBuf::from("dice.png")]);
+        assert_eq!(chunk_type.to_string(), "ruSt");
+        assert_eq!(message.message.as_deref(), Some("hello"));
+
+        let (paths, _, message) = encode(&["-", "ruSt", "@secret.txt"]).unwrap();
+        assert_eq!(paths, [PathBuf::from("-")]);
+        assert_eq!(message.message.as_deref(), Some("@secret.txt"));
+
+        let (paths, _, message) = encode(&["a.png", "b.png", "ruSt", "-m", "-not a flag"]).unwrap();
+        assert_eq!(paths, [PathBuf::from("a.png"), PathBuf::from("b.png")]);
+        assert_eq!(message.message.as_deref(), Some("-not a flag"));
+
+        let (paths, _, message) = encode(&["--prompt", "a.png", "ruSt"]).unwrap();
+        assert_eq!(paths, [PathBuf::from("a.png")]);
+        assert!(message.prompt && message.message.is_none());
+
+        assert_eq!(
+            encode(&["a.png", "ruSt"]).unwrap_err(),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            encode(&["a.png", "ruSt", "hello", "--prompt"]).unwrap_err(),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            encode(&["a.png", "rust", "hello"]).unwrap_err(),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn test_fixed_output_commands() {
+        let command = |args: &[&str]| {
+            Cli::try_parse_from(["pngme"].iter().chain(args))
+                .unwrap()
+                .command
+        };
+        assert_eq!(command(&["dump", "a.png"]).fixed_output(), Some("dump"));
+        assert_eq!(command(&["man"]).fixed_output(), Some("man"));
+        assert_eq!(command(&["man", "-o", "pages"]).fixed_output(), None);
+        assert_eq!(command(&["print", "a.png"]).fixed_output(), None);
+    }
+
+    #[test]
+    fn test_chunk_type_arguments() {
+        let cli = Cli::try_parse_from(["pngme", "decode", "a.png", "ruSt"]).unwrap();
+        assert!(
+            matches!(cli.command, Commands::Decode { chunk_type, .. } if chunk_type.to_string() == "ruSt")
+        );
+        let error = Cli::try_parse_from(["pngme", "remove", "a.png", "rust"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+        assert!(
+            error.to_string().contains("did you mean `ruSt`?"),
+            "{}",
+            error
+        );
     }
-    Ok(())
 }
@@ -0,0 +1,164 @@
+//! Decides where subcommands that modify PNG files write their results
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use serde_json::json;
+
+use pngme_lib::{Error, Png};
+
+use crate::batch::Report;
+use crate::streams::{create_png, is_stdio, save_png};
+
+/// Options shared by every subcommand that modifies PNG files, which otherwise overwrite
+/// their inputs
+#[derive(Debug, Args)]
+pub struct WriteOptions {
+    /// Write the result here instead of over the input. With several inputs, a directory
+    /// to write each result into under its original file name
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Report what would change without writing any files
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Copy each input to its path plus SUFFIX before overwriting it
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".bak",
+        conflicts_with = "output"
+    )]
+    pub backup: Option<String>,
+}
+
+impl WriteOptions {
+    /// The file the result for `input` is written to. `into_dir` treats the output path as
+    /// a directory, which is needed when there are several inputs.
+    pub fn destination(&self, input: &Path, into_dir: bool) -> PathBuf {
+        match &self.output {
+            Some(output) if into_dir || output.is_dir() => {
+                output.join(input.file_name().unwrap_or_default())
+            }
+            Some(output) => output.clone(),
+            None => input.to_path_buf(),
+        }
+    }
+
+    /// True if the result for the single input `input` is written to stdout
+    pub fn writes_stdout(&self, input: &Path) -> bool {
+        !self.dry_run && is_stdio(&self.destination(input, false))
+    }
+
+    /// Checks that several inputs can be written: the output directory is created, and
+    /// writing to stdout or writing inputs to the same file are rejected
+    pub fn prepare_batch(&self, inputs: &[PathBuf]) -> Result<(), String> {
+        let Some(output) = &self.output else {
+            return Ok(());
+        };
+        if is_stdio(output) {
+            return Err(
+                "--output - writes a single PNG to stdout, it cannot be used with several \
+                 files, directories or glob patterns"
+                    .to_string(),
+            );
+        }
+        if output.exists() && !output.is_dir() {
+            return Err(format!(
+                "--output must be a directory when several files are given, {} is a file",
+                output.display()
+            ));
+        }
+        let mut seen = HashSet::new();
+        for input in inputs {
+            let destination = self.destination(input, true);
+            if !seen.insert(destination.clone()) {
+                return Err(format!(
+                    "several inputs would be written to {}",
+                    destination.display()
+                ));
+            }
+        }
+        if !self.dry_run {
+            fs::create_dir_all(output)
+                .map_err(|e| format!("could not create directory {}: {}", output.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Writes `png`, the result for `input`, backing up the input first if asked to.
+    /// Returns the file written, or that would have been written on a dry run.
+    pub fn write(&self, input: &Path, into_dir: bool, png: &Png) -> Result<PathBuf, Error> {
+        let destination = self.destination(input, into_dir);
+        if self.dry_run {
+            return Ok(destination);
+        }
+        if let Some(suffix) = &self.backup {
+            if !is_stdio(input) {
+                let mut backup = OsString::from(input);
+                backup.push(suffix);
+                fs::copy(input, &backup).map_err(|source| Error::PNGWrite { source })?;
+            }
+        }
+        if destination == input {
+            save_png(input, png)?;
+        } else {
+            create_png(&destination, png)?;
+        }
+        Ok(destination)
+    }
+
+    /// Adds where the result went to `report`. Dry runs also say so in the text output.
+    pub fn annotate(&self, report: &mut Report, destination: &Path) {
+        if self.dry_run {
+            report.text += &format!("Dry run, would write {}\n", destination.display());
+        }
+        if let Some(fields) = report.json.as_object_mut() {
+            fields.insert("output".to_string(), json!(destination));
+            fields.insert("dry_run".to_string(), json!(self.dry_run));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(output: Option<&str>) -> WriteOptions {
+        WriteOptions {
+            output: output.map(PathBuf::from),
+            dry_run: true,
+            backup: None,
+        }
+    }
+
+    #[test]
+    fn test_destination() {
+        let input = Path::new("assets/dice.png");
+        assert_eq!(options(None).destination(input, false), input);
+        assert_eq!(
+            options(Some("out.png")).destination(input, false),
+            Path::new("out.png")
+        );
+        assert_eq!(
+            options(Some("out")).destination(input, true),
+            Path::new("out/dice.png")
+        );
+        let mut to_stdout = options(Some("-"));
+        assert!(!to_stdout.writes_stdout(Path::new("dice.png")));
+        to_stdout.dry_run = false;
+        assert!(to_stdout.writes_stdout(Path::new("dice.png")));
+    }
+
+    #[test]
+    fn test_prepare_batch_rejects_clashes() {
+        let inputs = [PathBuf::from("a/dice.png"), PathBuf::from("b/dice.png")];
+        assert!(options(None).prepare_batch(&inputs).is_ok());
+        assert!(options(Some("out")).prepare_batch(&inputs).is_err());
+        assert!(options(Some("out")).prepare_batch(&inputs[..1]).is_ok());
+        assert!(options(Some("-")).prepare_batch(&inputs[..1]).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Resolves path arguments to files or, when given `-`, to stdin and stdout
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use pngme_lib::{read_png, read_png_from, write_png, write_png_to, Error, Png};
+
+/// The argument value that stands for stdin or stdout
+pub const STDIO: &str = "-";
+
+/// Returns true if `path` stands for stdin or stdout
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO)
+}
+
+/// Reads the raw bytes of the file at `path`, or of stdin if `path` is `-`
+pub fn load_bytes(path: &Path) -> Result<Vec<u8>, Error> {
+    if is_stdio(path) {
+        read_payload(STDIO)
+    } else {
+        fs::read(path).map_err(|source| Error::FileNotFound {
+            source,
+            path: path.into(),
+        })
+    }
+}
+
+/// Reads a PNG from the file at `path`, or from stdin if `path` is `-`
+pub fn load_png(path: &Path) -> Result<Png, Error> {
+    if is_stdio(path) {
+        read_png_from(io::stdin().lock())
+    } else {
+        read_png(path)
+    }
+}
+
+/// Writes a PNG over the file at `path`, or to stdout if `path` is `-`
+pub fn save_png(path: &Path, png: &Png) -> Result<(), Error> {
+    if is_stdio(path) {
+        write_png_to(io::stdout().lock(), png)
+    } else {
+        write_png(path, png)
+    }
+}
+
+/// Writes a PNG to the file at `path`, creating or replacing it, or to stdout if `path`
+/// is `-`
+pub fn create_png(path: &Path, png: &Png) -> Result<(), Error> {
+    if is_stdio(path) {
+        write_png_to(io::stdout().lock(), png)
+    } else {
+        let file = fs::File::create(path).map_err(|source| Error::PNGWrite { source })?;
+        write_png_to(file, png)
+    }
+}
+
+/// Resolves a message argument to its bytes: `-` reads stdin, `@path` reads the file at
+/// `path` and anything else is used as the message itself
+pub fn read_payload(message: &str) -> Result<Vec<u8>, Error> {
+    if message == STDIO {
+        let mut data = vec![];
+        io::stdin()
+            .lock()
+            .read_to_end(&mut data)
+            .map_err(|source| Error::Read { source })?;
+        Ok(data)
+    } else if let Some(path) = message.strip_prefix('@') {
+        if path == STDIO {
+            return read_payload(STDIO);
+        }
+        fs::read(path).map_err(|source| Error::FileNotFound {
+            source,
+            path: path.into(),
+        })
+    } else {
+        Ok(message.as_bytes().to_vec())
+    }
+}
+
+/// Returns true if resolving `message` with [`read_payload`] consumes stdin
+pub fn payload_uses_stdin(message: &str) -> bool {
+    message == STDIO || message == "@-"
+}
@@ -0,0 +1,643 @@
+//! Interactive terminal editor for the chunks of a PNG file
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use pngme_lib::{
+    hexdump, read_png_from, validate_bytes, write_png, Chunk, ChunkType, Error, Ihdr, Png, Problem,
+};
+
+use crate::streams::{load_bytes, payload_uses_stdin, read_payload};
+
+/// Only this many bytes of a chunk are previewed, so huge `IDAT` chunks stay responsive
+const PREVIEW_LIMIT: usize = 4096;
+
+const HELP: &str =
+    "↑↓ select  J/K move  a add  d delete  e export  x hex/text  u undo  s save  q quit";
+
+/// The chunks being edited along with the CRCs that did not match when the file was read
+#[derive(Clone)]
+struct Document {
+    png: Png,
+    /// The stored CRC of each chunk whose CRC was wrong in the file, kept in step with
+    /// `png` as chunks are added, removed and moved
+    bad_crcs: Vec<Option<u32>>,
+}
+
+enum Mode {
+    Normal,
+    /// Waiting for the type of a chunk to add
+    AddType(String),
+    /// Waiting for the data of a chunk to add
+    AddData(ChunkType, String),
+    /// Waiting for the file to export the selected chunk to
+    Export(String),
+    /// Quit was pressed with unsaved changes
+    ConfirmQuit,
+}
+
+/// State of the editor, independent of the terminal
+pub struct App {
+    path: PathBuf,
+    doc: Document,
+    undo: Vec<Document>,
+    table: TableState,
+    mode: Mode,
+    hex: bool,
+    scroll: u16,
+    dirty: bool,
+    status: String,
+    quit: bool,
+}
+
+impl App {
+    /// Reads the PNG at `path`, noting any chunks whose stored CRC is wrong
+    pub fn open(path: PathBuf) -> Result<App, Error> {
+        let bytes = load_bytes(&path)?;
+        let png = read_png_from(bytes.as_slice())?;
+        // Saving writes the correct CRCs, the bad ones are only shown until then
+        let png = Png::from_chunks(
+            png.chunks()
+                .iter()
+                .map(|chunk| chunk.clone().with_stored_crc(chunk.crc()))
+                .collect(),
+        );
+        let mut bad_crcs = vec![None; png.chunks().len()];
+        for issue in validate_bytes(&bytes) {
+            if let (Some(index), Problem::CrcMismatch { stored, .. }) =
+                (issue.chunk_index, issue.problem)
+            {
+                if let Some(slot) = bad_crcs.get_mut(index) {
+                    *slot = Some(stored);
+                }
+            }
+        }
+        let mut table = TableState::default();
+        table.select((!png.chunks().is_empty()).then_some(0));
+        Ok(App {
+            path,
+            doc: Document { png, bad_crcs },
+            undo: vec![],
+            table,
+            mode: Mode::Normal,
+            hex: false,
+            scroll: 0,
+            dirty: false,
+            status: String::new(),
+            quit: false,
+        })
+    }
+
+    fn chunks(&self) -> &[Chunk] {
+        self.doc.png.chunks()
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.table.selected().filter(|&i| i < self.chunks().len())
+    }
+
+    fn select(&mut self, index: Option<usize>) {
+        self.table.select(index);
+        self.scroll = 0;
+    }
+
+    /// Saves the current state so the next edit can be undone
+    fn checkpoint(&mut self) {
+        self.undo.push(self.doc.clone());
+        self.dirty = true;
+    }
+
+    fn select_by(&mut self, delta: isize) {
+        let len = self.chunks().len();
+        if len == 0 {
+            return;
+        }
+        let current = self.selected().unwrap_or(0) as isize;
+        self.select(Some((current + delta).clamp(0, len as isize - 1) as usize));
+    }
+
+    /// Moves the selected chunk up (`-1`) or down (`1`)
+    fn move_selected(&mut self, delta: isize) {
+        let Some(from) = self.selected() else { return };
+        let to = from as isize + delta;
+        if to < 0 || to as usize >= self.chunks().len() {
+            return;
+        }
+        let to = to as usize;
+        self.checkpoint();
+        self.doc.png.move_chunk(from, to);
+        let crc = self.doc.bad_crcs.remove(from);
+        self.doc.bad_crcs.insert(to, crc);
+        self.table.select(Some(to));
+    }
+
+    fn delete_selected(&mut self) {
+        let Some(index) = self.selected() else { return };
+        self.checkpoint();
+        let chunk = self.doc.png.remove_chunk(index);
+        self.doc.bad_crcs.remove(index);
+        self.status = if chunk.chunk_type().is_critical() {
+            format!(
+                "Removed critical chunk {}, press u to undo",
+                chunk.chunk_type()
+            )
+        } else {
+            format!("Removed {}", chunk.chunk_type())
+        };
+        let len = self.chunks().len();
+        self.select(if len == 0 {
+            None
+        } else {
+            Some(index.min(len - 1))
+        });
+    }
+
+    /// Inserts a chunk after the selected one, keeping `IEND` last
+    fn add_chunk(&mut self, chunk: Chunk) {
+        let index = match self.selected() {
+            Some(i) if self.chunks()[i].chunk_type().bytes() == *b"IEND" => i,
+            Some(i) => i + 1,
+            None => self.chunks().len(),
+        };
+        self.checkpoint();
+        self.status = format!("Added {} at {}", chunk.chunk_type(), index);
+        self.doc.png.insert_chunk(index, chunk);
+        self.doc.bad_crcs.insert(index, None);
+        self.select(Some(index));
+    }
+
+    fn export_selected(&mut self, path: &Path) {
+        let Some(index) = self.selected() else { return };
+        let chunk = &self.chunks()[index];
+        self.status = match fs::write(path, chunk.data()) {
+            Ok(()) => format!("Wrote {} bytes to {}", chunk.length(), path.display()),
+            Err(e) => format!("Could not write {}: {}", path.display(), e),
+        };
+    }
+
+    fn undo(&mut self) {
+        match self.undo.pop() {
+            Some(doc) => {
+                self.doc = doc;
+                self.dirty = true;
+                self.status = "Undone".to_string();
+                let len = self.chunks().len();
+                let selected = self.selected().or(Some(0)).filter(|_| len > 0);
+                self.select(selected.map(|i| i.min(len - 1)));
+            }
+            None => self.status = "Nothing to undo".to_string(),
+        }
+    }
+
+    fn save(&mut self) {
+        match write_png(&self.path, &self.doc.png) {
+            Ok(()) => {
+                self.dirty = false;
+                self.doc.bad_crcs.fill(None);
+                self.status = format!("Saved {}", self.path.display());
+            }
+            Err(e) => self.status = format!("Could not save: {}", e),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+        self.mode = match mode {
+            Mode::Normal => {
+                self.status.clear();
+                self.handle_normal_key(key.code)
+            }
+            Mode::ConfirmQuit => match key.code {
+                KeyCode::Char('q') => {
+                    self.quit = true;
+                    Mode::Normal
+                }
+                KeyCode::Char('s') => {
+                    self.save();
+                    self.quit = !self.dirty;
+                    Mode::Normal
+                }
+                _ => {
+                    self.status.clear();
+                    Mode::Normal
+                }
+            },
+            Mode::AddType(input) => match edit_input(input, key.code) {
+                Input::Editing(input) => Mode::AddType(input),
+                Input::Cancelled => Mode::Normal,
+                Input::Done(input) => match ChunkType::from_str(&input) {
+                    Ok(chunk_type) => Mode::AddData(chunk_type, String::new()),
+                    Err(e) => {
+                        self.status = format!("Invalid chunk type {:?}: {}", input, e);
+                        Mode::AddType(input)
+                    }
+                },
+            },
+            Mode::AddData(chunk_type, input) => match edit_input(input, key.code) {
+                Input::Editing(input) => Mode::AddData(chunk_type, input),
+                Input::Cancelled => Mode::Normal,
+                Input::Done(input) if payload_uses_stdin(&input) => {
+                    self.status = "stdin cannot be read while editing".to_string();
+                    Mode::AddData(chunk_type, input)
+                }
+                Input::Done(input) => match read_payload(&input) {
+                    Ok(data) => {
+                        self.add_chunk(Chunk::new(chunk_type, data));
+                        Mode::Normal
+                    }
+                    Err(e) => {
+                        self.status = e.to_string();
+                        Mode::AddData(chunk_type, input)
+                    }
+                },
+            },
+            Mode::Export(input) => match edit_input(input, key.code) {
+                Input::Editing(input) => Mode::Export(input),
+                Input::Cancelled => Mode::Normal,
+                Input::Done(input) => {
+                    self.export_selected(Path::new(&input));
+                    Mode::Normal
+                }
+            },
+        };
+    }
+
+    fn handle_normal_key(&mut self, code: KeyCode) -> Mode {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc if self.dirty => {
+                self.status = "Unsaved changes: q to quit anyway, s to save and quit".to_string();
+                return Mode::ConfirmQuit;
+            }
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.select_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.select_by(1),
+            KeyCode::Home => self.select_by(isize::MIN / 2),
+            KeyCode::End => self.select_by(isize::MAX / 2),
+            KeyCode::Char('K') => self.move_selected(-1),
+            KeyCode::Char('J') => self.move_selected(1),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(10),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::Char('x') => self.hex = !self.hex,
+            KeyCode::Char('d') | KeyCode::Delete => self.delete_selected(),
+            KeyCode::Char('u') => self.undo(),
+            KeyCode::Char('s') => self.save(),
+            KeyCode::Char('a') => return Mode::AddType(String::new()),
+            KeyCode::Char('e') => {
+                if let Some(index) = self.selected() {
+                    let chunk_type = self.chunks()[index].chunk_type();
+                    return Mode::Export(format!("{}-{}.bin", chunk_type, index));
+                }
+            }
+            _ => {}
+        }
+        Mode::Normal
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [title, main, status, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list, right] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(main);
+        let [header, preview] =
+            Layout::vertical([Constraint::Length(9), Constraint::Min(3)]).areas(right);
+
+        let size: usize = self.chunks().iter().map(|c| c.as_bytes().len()).sum();
+        frame.render_widget(
+            Line::from(format!(
+                " pngme: {}{} ({} chunks, {} bytes)",
+                self.path.display(),
+                if self.dirty { " [modified]" } else { "" },
+                self.chunks().len(),
+                size + 8
+            ))
+            .bold(),
+            title,
+        );
+        self.draw_chunks(frame, list);
+        self.draw_ihdr(frame, header);
+        self.draw_preview(frame, preview);
+
+        let prompt = match &self.mode {
+            Mode::AddType(input) => Some(format!("New chunk type: {}", input)),
+            Mode::AddData(chunk_type, input) => Some(format!(
+                "{} data (text, or @FILE to read a file): {}",
+                chunk_type, input
+            )),
+            Mode::Export(input) => Some(format!("Export data to: {}", input)),
+            _ => None,
+        };
+        match prompt {
+            Some(prompt) => {
+                frame.render_widget(Line::from(prompt.clone()).yellow(), status);
+                frame.set_cursor_position((status.x + prompt.chars().count() as u16, status.y));
+            }
+            None => frame.render_widget(Line::from(self.status.clone()).yellow(), status),
+        }
+        frame.render_widget(Line::from(HELP).dim(), help);
+    }
+
+    fn draw_chunks(&mut self, frame: &mut Frame, area: Rect) {
+        let rows: Vec<Row> = self
+            .chunks()
+            .iter()
+            .zip(&self.doc.bad_crcs)
+            .enumerate()
+            .map(|(index, (chunk, bad_crc))| {
+                let crc = match bad_crc {
+                    Some(_) => Cell::from("bad").red(),
+                    None => Cell::from("ok").green(),
+                };
+                Row::new(vec![
+                    Cell::from(index.to_string()),
+                    Cell::from(chunk.chunk_type().to_string()),
+                    Cell::from(flags(chunk.chunk_type())),
+                    Cell::from(chunk.length().to_string()),
+                    crc,
+                ])
+            })
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(4),
+                Constraint::Length(5),
+                Constraint::Length(5),
+                Constraint::Min(6),
+                Constraint::Length(4),
+            ],
+        )
+        .header(Row::new(["#", "Type", "Flags", "Size", "CRC"]).bold())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::new().borders(Borders::ALL).title(" Chunks "));
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_ihdr(&self, frame: &mut Frame, area: Rect) {
+        let lines = match self.chunks().iter().find_map(Ihdr::from_chunk) {
+            Some(ihdr) => vec![
+                format!("Size:        {} x {}", ihdr.width, ihdr.height),
+                format!("Bit depth:   {}", ihdr.bit_depth),
+                format!(
+                    "Color type:  {} ({})",
+                    ihdr.color_type,
+                    ihdr.color_type_name()
+                ),
+                format!("Compression: {}", ihdr.compression_method),
+                format!("Filter:      {}", ihdr.filter_method),
+                format!(
+                    "Interlace:   {}",
+                    match ihdr.interlace_method {
+                        0 => "none",
+                        1 => "Adam7",
+                        _ => "unknown",
+                    }
+                ),
+            ],
+            None => vec!["No valid IHDR chunk".to_string()],
+        };
+        let text = Text::from(lines.into_iter().map(Line::from).collect::<Vec<_>>());
+        frame.render_widget(
+            Paragraph::new(text).block(Block::new().borders(Borders::ALL).title(" IHDR ")),
+            area,
+        );
+    }
+
+    fn draw_preview(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::new().borders(Borders::ALL);
+        let Some(index) = self.selected() else {
+            frame.render_widget(Paragraph::new("No chunks").block(block), area);
+            return;
+        };
+        let chunk = &self.chunks()[index];
+        let chunk_type = chunk.chunk_type();
+        let mut lines = vec![
+            Line::from(format!(
+                "{}, {}, {}, {}",
+                if chunk_type.is_critical() {
+                    "critical"
+                } else {
+                    "ancillary"
+                },
+                if chunk_type.is_public() {
+                    "public"
+                } else {
+                    "private"
+                },
+                if chunk_type.is_reserved_bit_valid() {
+                    "reserved bit ok"
+                } else {
+                    "reserved bit set"
+                },
+                if chunk_type.is_safe_to_copy() {
+                    "safe to copy"
+                } else {
+                    "unsafe to copy"
+                },
+            )),
+            match self.doc.bad_crcs[index] {
+                Some(stored) => Line::from(format!(
+                    "CRC {:#010x} stored, {:#010x} expected (fixed on save)",
+                    stored,
+                    chunk.crc()
+                ))
+                .red(),
+                None => Line::from(format!("CRC {:#010x}", chunk.crc())),
+            },
+            Line::default(),
+        ];
+
+        let data = &chunk.data()[..chunk.data().len().min(PREVIEW_LIMIT)];
+        let text = std::str::from_utf8(data).ok().filter(|text| {
+            !text
+                .chars()
+                .any(|c| c.is_control() && c != '\n' && c != '\t')
+        });
+        let (mode, body) = match text {
+            Some(text) if !self.hex => ("text", text.to_string()),
+            _ => ("hex", hexdump(data)),
+        };
+        lines.extend(body.lines().map(|line| Line::from(line.to_string())));
+        if chunk.data().len() > PREVIEW_LIMIT {
+            lines.push(
+                Line::from(format!(
+                    "... {} more bytes",
+                    chunk.data().len() - PREVIEW_LIMIT
+                ))
+                .dim(),
+            );
+        }
+        let title = format!(" {} #{} ({}) ", chunk_type, index, mode);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(block.title(title))
+                .wrap(Wrap { trim: false })
+                .scroll((self.scroll, 0)),
+            area,
+        );
+    }
+}
+
+/// Short flags for a chunk type: critical or ancillary, public or private, reserved bit
+/// and safe to copy, each shown as a letter when set and `-` otherwise
+fn flags(chunk_type: &ChunkType) -> String {
+    [
+        (chunk_type.is_critical(), 'C'),
+        (chunk_type.is_public(), 'P'),
+        (!chunk_type.is_reserved_bit_valid(), 'R'),
+        (chunk_type.is_safe_to_copy(), 'S'),
+    ]
+    .iter()
+    .map(|&(set, flag)| if set { flag } else { '-' })
+    .collect()
+}
+
+enum Input {
+    Editing(String),
+    Done(String),
+    Cancelled,
+}
+
+fn edit_input(mut input: String, code: KeyCode) -> Input {
+    match code {
+        KeyCode::Enter => return Input::Done(input),
+        KeyCode::Esc => return Input::Cancelled,
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        _ => {}
+    }
+    Input::Editing(input)
+}
+
+/// Runs the editor until the user quits
+pub fn run(mut app: App) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> io::Result<()> {
+    while !app.quit {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.into())
+    }
+
+    fn app() -> App {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", ""),
+            chunk("tEXt", "hello"),
+            chunk("IDAT", ""),
+            chunk("IEND", ""),
+        ]);
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("app.png");
+        fs::write(&path, png.as_bytes()).unwrap();
+        App::open(path).unwrap()
+    }
+
+    fn types(app: &App) -> Vec<String> {
+        app.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::from(code));
+        }
+    }
+
+    #[test]
+    fn test_move_delete_undo() {
+        let mut app = app();
+        press(&mut app, "jJ");
+        assert_eq!(types(&app), ["IHDR", "IDAT", "tEXt", "IEND"]);
+        assert_eq!(app.selected(), Some(2));
+        press(&mut app, "d");
+        assert_eq!(types(&app), ["IHDR", "IDAT", "IEND"]);
+        assert!(app.dirty);
+        press(&mut app, "uu");
+        assert_eq!(types(&app), ["IHDR", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(app.doc.bad_crcs.len(), 4);
+    }
+
+    #[test]
+    fn test_add_before_iend() {
+        let mut app = app();
+        press(&mut app, "jjjaruSt\nsecret\n");
+        assert_eq!(types(&app), ["IHDR", "tEXt", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(app.chunks()[3].data(), b"secret");
+    }
+
+    #[test]
+    fn test_quit_asks_when_dirty() {
+        let mut app = app();
+        press(&mut app, "dq");
+        assert!(!app.quit);
+        press(&mut app, "q");
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut app = app();
+        press(&mut app, "jx");
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("tEXt #1 (hex)"));
+        assert!(screen.contains("68 65 6c 6c 6f"));
+    }
+
+    #[test]
+    fn test_flags() {
+        assert_eq!(flags(&ChunkType::from_str("IHDR").unwrap()), "CP--");
+        assert_eq!(flags(&ChunkType::from_str("ruSt").unwrap()), "---S");
+    }
+}
@@ -0,0 +1,334 @@
+//! Pixel views for spotting data hidden in the image itself: bit planes, the difference
+//! from a cover image and how much the low bits could hold
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+use pngme_lib::{Pixels, Png};
+
+use crate::worker::Worker;
+
+/// Pixels decoded from one revision of a document, or why they could not be
+type Decoded = (u64, Result<Pixels, String>);
+
+/// The image the open file is compared against, usually the original before data was
+/// hidden in it
+struct Cover {
+    path: PathBuf,
+    pixels: Result<Pixels, String>,
+    /// The heatmap and the revision of the document it was drawn for
+    heatmap: Option<(u64, Result<Heatmap, String>)>,
+}
+
+struct Heatmap {
+    texture: egui::TextureHandle,
+    changed: usize,
+}
+
+/// Settings and cached textures of the pixel views of one document
+pub struct Analysis {
+    decoded: Option<Decoded>,
+    /// The revision last handed to the worker, so a cancelled decode is not restarted
+    decoding: Option<u64>,
+    channel: usize,
+    /// The bit plane shown, 0 for the least significant bit
+    bit: u8,
+    /// Low bits per sample counted by the capacity meter
+    capacity_bits: u8,
+    /// The bit plane texture and the revision, channel and bit it shows
+    plane: Option<((u64, usize, u8), egui::TextureHandle)>,
+    cover: Option<Cover>,
+}
+
+impl Default for Analysis {
+    fn default() -> Self {
+        Self {
+            decoded: None,
+            decoding: None,
+            channel: 0,
+            bit: 0,
+            capacity_bits: 1,
+            plane: None,
+            cover: None,
+        }
+    }
+}
+
+impl Analysis {
+    /// Draws the views for `revision` of `png`, the document at `document`. Pixels are
+    /// decoded by `worker` the first time they are shown after an edit. `payload` is the
+    /// size of the message about to be encoded.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        worker: &mut Worker,
+        document: &Path,
+        png: &Png,
+        revision: u64,
+        payload: usize,
+    ) {
+        let decoded = match self.decoded.take() {
+            Some((decoded_revision, pixels)) if decoded_revision == revision => pixels,
+            _ => {
+                self.start_decoding(ui, worker, document, png, revision);
+                return;
+            }
+        };
+        match &decoded {
+            Ok(pixels) => {
+                ui.label(pixels.ihdr().to_string());
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    capacity(ui, pixels, &mut self.capacity_bits, payload);
+                    ui.separator();
+                    self.bit_plane(ui, pixels, revision);
+                    ui.separator();
+                    self.comparison(ui, worker, document, pixels, revision);
+                });
+            }
+            Err(e) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+        }
+        self.decoded = Some((revision, decoded));
+    }
+
+    /// Stores the pixels the worker decoded for `revision`
+    pub fn decoded(&mut self, revision: u64, pixels: Result<Pixels, String>) {
+        self.decoded = Some((revision, pixels));
+    }
+
+    /// Stores the cover image the worker decoded from `path`
+    pub fn cover_decoded(&mut self, path: PathBuf, pixels: Result<Pixels, String>) {
+        self.cover = Some(Cover {
+            path,
+            pixels,
+            heatmap: None,
+        });
+    }
+
+    /// Hands `revision` of `png` to the worker once it is free, unless decoding it was
+    /// cancelled
+    fn start_decoding(
+        &mut self,
+        ui: &mut egui::Ui,
+        worker: &mut Worker,
+        document: &Path,
+        png: &Png,
+        revision: u64,
+    ) {
+        if worker.running().is_some() {
+            ui.label("Decoding pixels…");
+        } else if self.decoding == Some(revision) {
+            ui.label("Decoding the pixels was cancelled.");
+            if ui.button("Decode pixels").clicked() {
+                self.decoding = None;
+            }
+        } else {
+            self.decoding = Some(revision);
+            worker.decode(ui.ctx(), document.to_path_buf(), revision, png.clone());
+            ui.label("Decoding pixels…");
+        }
+    }
+
+    fn bit_plane(&mut self, ui: &mut egui::Ui, pixels: &Pixels, revision: u64) {
+        let names = pixels.channel_names();
+        self.channel = self.channel.min(names.len() - 1);
+        self.bit = self.bit.min(pixels.ihdr().bit_depth - 1);
+        ui.horizontal(|ui| {
+            ui.strong("Bit plane");
+            egui::ComboBox::from_id_salt("channel")
+                .selected_text(names[self.channel])
+                .show_ui(ui, |ui| {
+                    for (index, name) in names.iter().enumerate() {
+                        ui.selectable_value(&mut self.channel, index, *name);
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut self.bit, 0..=pixels.ihdr().bit_depth - 1)
+                    .text("bit, 0 is the lowest"),
+            );
+        });
+        if let Err(e) = fits(ui, pixels) {
+            ui.label(e);
+            return;
+        }
+        let key = (revision, self.channel, self.bit);
+        if self.plane.as_ref().map(|(k, _)| *k) != Some(key) {
+            // The channel and bit were clamped to the image above
+            let Some(plane) = pixels.bit_plane(self.channel, self.bit) else {
+                return;
+            };
+            let plane: Vec<u8> = plane
+                .into_iter()
+                .map(|set| if set { 255 } else { 0 })
+                .collect();
+            let image = egui::ColorImage::from_gray(size(pixels), &plane);
+            let texture = ui
+                .ctx()
+                .load_texture("bit plane", image, egui::TextureOptions::NEAREST);
+            self.plane = Some((key, texture));
+        }
+        if let Some((_, texture)) = &self.plane {
+            show_image(ui, texture);
+        }
+    }
+
+    fn comparison(
+        &mut self,
+        ui: &mut egui::Ui,
+        worker: &mut Worker,
+        document: &Path,
+        pixels: &Pixels,
+        revision: u64,
+    ) {
+        ui.horizontal(|ui| {
+            ui.strong("Difference from cover image");
+            let idle = worker.running().is_none();
+            if ui
+                .add_enabled(idle, egui::Button::new("Choose cover image…"))
+                .clicked()
+            {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("PNG image", &["png"])
+                    .pick_file()
+                {
+                    worker.decode_cover(ui.ctx(), document.to_path_buf(), path);
+                }
+            }
+            if self.cover.is_some() && ui.button("Clear").clicked() {
+                self.cover = None;
+            }
+        });
+        let Some(cover) = &mut self.cover else {
+            ui.label("Pick the original image to see which pixels changed.");
+            return;
+        };
+        ui.label(cover.path.display().to_string());
+        let cover_pixels = match &cover.pixels {
+            Ok(cover_pixels) => cover_pixels,
+            Err(e) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+                return;
+            }
+        };
+        if cover.heatmap.as_ref().map(|(r, _)| *r) != Some(revision) {
+            let heatmap = fits(ui, pixels).and_then(|()| heatmap(ui.ctx(), cover_pixels, pixels));
+            cover.heatmap = Some((revision, heatmap));
+        }
+        match &cover.heatmap {
+            Some((_, Ok(heatmap))) => {
+                let total = pixels.width() as usize * pixels.height() as usize;
+                ui.label(format!(
+                    "{} of {} pixels differ, from yellow for the smallest changes to red for the largest",
+                    heatmap.changed, total
+                ));
+                show_image(ui, &heatmap.texture);
+            }
+            Some((_, Err(e))) => {
+                ui.label(e);
+            }
+            None => {}
+        }
+    }
+}
+
+/// How many bytes the low bits of the image hold compared to the message being encoded
+fn capacity(ui: &mut egui::Ui, pixels: &Pixels, bits: &mut u8, payload: usize) {
+    let bit_depth = pixels.ihdr().bit_depth;
+    *bits = (*bits).clamp(1, bit_depth);
+    ui.horizontal(|ui| {
+        ui.strong("Capacity");
+        ui.add(egui::Slider::new(bits, 1..=bit_depth).text("low bits per sample"));
+    });
+    let capacity = pixels.lsb_capacity(*bits);
+    ui.label(format!(
+        "{} bytes fit in the lowest {} bit(s) of each color sample",
+        capacity, bits
+    ));
+    let fill = if capacity == 0 {
+        1.0
+    } else {
+        payload as f32 / capacity as f32
+    };
+    ui.add(
+        egui::ProgressBar::new(fill.min(1.0)).text(format!("Current message: {} bytes", payload)),
+    );
+    if payload > capacity {
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            "The message is larger than the image can hold at this depth",
+        );
+    }
+}
+
+/// Draws the difference between the pixels of `cover` and `stego`, black where they
+/// are equal
+fn heatmap(ctx: &egui::Context, cover: &Pixels, stego: &Pixels) -> Result<Heatmap, String> {
+    let difference = cover
+        .difference(stego)
+        .ok_or("The images differ in size, color type or bit depth")?;
+    let largest = difference.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let pixels = difference
+        .iter()
+        .map(|&d| match d {
+            0 => egui::Color32::BLACK,
+            d => egui::Color32::from_rgb(255, (255.0 * (1.0 - d as f32 / largest)) as u8, 0),
+        })
+        .collect();
+    let image = egui::ColorImage {
+        size: size(stego),
+        pixels,
+    };
+    Ok(Heatmap {
+        texture: ctx.load_texture("heatmap", image, egui::TextureOptions::NEAREST),
+        changed: difference.iter().filter(|&&d| d > 0).count(),
+    })
+}
+
+fn size(pixels: &Pixels) -> [usize; 2] {
+    [pixels.width() as usize, pixels.height() as usize]
+}
+
+/// Checks that the image can be uploaded as a texture
+fn fits(ui: &egui::Ui, pixels: &Pixels) -> Result<(), String> {
+    let max = ui.ctx().input(|i| i.max_texture_side);
+    if size(pixels).iter().any(|&side| side > max) {
+        return Err(format!("Images larger than {0}x{0} cannot be shown", max));
+    }
+    Ok(())
+}
+
+/// Shows `texture` as wide as the panel allows, without scaling it up
+fn show_image(ui: &mut egui::Ui, texture: &egui::TextureHandle) {
+    let size = texture.size_vec2();
+    let scale = (ui.available_width() / size.x).min(1.0);
+    ui.add(egui::Image::new(texture).fit_to_exact_size(size * scale));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme_lib::read_png;
+    use std::path::Path;
+
+    #[test]
+    fn test_decode_matches_image_crate() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../pngme/dice.png");
+        let pixels = Pixels::decode(&read_png(&path).unwrap()).unwrap();
+        let expected = image::open(&path).unwrap().to_rgba8();
+        assert_eq!((pixels.width(), pixels.height()), expected.dimensions());
+        for (x, y, pixel) in expected.enumerate_pixels() {
+            for channel in 0..4 {
+                assert_eq!(pixels.sample(x, y, channel), pixel[channel] as u16);
+            }
+        }
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let ctx = egui::Context::default();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../pngme/dice.png");
+        let pixels = Pixels::decode(&read_png(&path).unwrap()).unwrap();
+        let heatmap = heatmap(&ctx, &pixels, &pixels).unwrap();
+        assert_eq!(heatmap.changed, 0);
+    }
+}
@@ -0,0 +1,172 @@
+//! The image preview and chunk table shown for the open file
+use eframe::egui;
+use pngme_lib::{describe_chunk, hexdump, Chunk, Png};
+
+use crate::chunk_type_flags;
+
+/// Largest width or height of the preview, in points
+const THUMBNAIL_SIZE: f32 = 160.0;
+/// Chunk data shown in the details is cut off after this many bytes, which keeps the
+/// large `IDAT` chunks responsive
+const MAX_SHOWN_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Index,
+    Type,
+    Length,
+    Crc,
+}
+
+/// Preview and chunk table state, rebuilt whenever a file is opened
+pub struct Browser {
+    thumbnail: Result<egui::TextureHandle, String>,
+    bad_crcs: Vec<bool>,
+    sort: SortKey,
+    descending: bool,
+    selected: Option<usize>,
+    hex: bool,
+}
+
+impl Browser {
+    /// Shows `thumbnail` as the preview. `bad_crcs` marks the chunks whose CRC does not
+    /// match their data.
+    pub fn new(
+        ctx: &egui::Context,
+        thumbnail: Result<egui::ColorImage, String>,
+        bad_crcs: Vec<bool>,
+    ) -> Self {
+        Self {
+            thumbnail: thumbnail
+                .map(|image| ctx.load_texture("thumbnail", image, egui::TextureOptions::default())),
+            bad_crcs,
+            sort: SortKey::Index,
+            descending: false,
+            selected: None,
+            hex: false,
+        }
+    }
+
+    /// Forgets the selection after the chunks changed. Chunks written by the library
+    /// always have valid CRCs.
+    pub fn chunks_changed(&mut self, chunk_count: usize) {
+        self.bad_crcs = vec![false; chunk_count];
+        self.selected = None;
+    }
+
+    /// Draws the preview, the chunk table and the data of the selected chunk
+    pub fn show(&mut self, ui: &mut egui::Ui, png: &Png) {
+        ui.horizontal_top(|ui| {
+            match &self.thumbnail {
+                Ok(texture) => {
+                    ui.add(egui::Image::new(texture).max_size(egui::Vec2::splat(THUMBNAIL_SIZE)));
+                }
+                Err(e) => {
+                    ui.label(format!("No preview: {}", e));
+                }
+            }
+            ui.vertical(|ui| self.table(ui, png));
+        });
+        if let Some(chunk) = self.selected.and_then(|index| png.chunk_at(index)) {
+            ui.separator();
+            self.details(ui, chunk);
+        }
+    }
+
+    fn sorted(&self, chunks: &[Chunk]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..chunks.len()).collect();
+        match self.sort {
+            SortKey::Index => {}
+            SortKey::Type => order.sort_by_key(|&i| chunks[i].chunk_type().to_string()),
+            SortKey::Length => order.sort_by_key(|&i| chunks[i].length()),
+            SortKey::Crc => order.sort_by_key(|&i| !self.crc_bad(i)),
+        }
+        if self.descending {
+            order.reverse();
+        }
+        order
+    }
+
+    fn crc_bad(&self, index: usize) -> bool {
+        self.bad_crcs.get(index).copied().unwrap_or(false)
+    }
+
+    fn header(&mut self, ui: &mut egui::Ui, key: SortKey, title: &str) {
+        let arrow = match (self.sort == key, self.descending) {
+            (true, false) => " ▲",
+            (true, true) => " ▼",
+            (false, _) => "",
+        };
+        if ui.button(format!("{}{}", title, arrow)).clicked() {
+            if self.sort == key {
+                self.descending = !self.descending;
+            } else {
+                self.sort = key;
+                self.descending = false;
+            }
+        }
+    }
+
+    fn table(&mut self, ui: &mut egui::Ui, png: &Png) {
+        let chunks = png.chunks();
+        egui::ScrollArea::vertical()
+            .id_salt("chunk table")
+            .max_height(240.0)
+            .show(ui, |ui| {
+                egui::Grid::new("chunks").striped(true).show(ui, |ui| {
+                    self.header(ui, SortKey::Index, "#");
+                    self.header(ui, SortKey::Type, "Type");
+                    self.header(ui, SortKey::Length, "Length");
+                    self.header(ui, SortKey::Crc, "CRC");
+                    ui.label("Flags");
+                    ui.end_row();
+                    for index in self.sorted(chunks) {
+                        let chunk = &chunks[index];
+                        let selected = self.selected == Some(index);
+                        if ui.selectable_label(selected, index.to_string()).clicked() {
+                            self.selected = if selected { None } else { Some(index) };
+                        }
+                        ui.monospace(chunk.chunk_type().to_string());
+                        ui.label(chunk.length().to_string());
+                        if self.crc_bad(index) {
+                            ui.colored_label(ui.visuals().error_fg_color, "mismatch");
+                        } else {
+                            ui.label(format!("{:#010x}", chunk.crc()));
+                        }
+                        ui.label(chunk_type_flags(chunk.chunk_type()));
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    fn details(&mut self, ui: &mut egui::Ui, chunk: &Chunk) {
+        ui.horizontal(|ui| {
+            ui.strong(format!("{} ({} bytes)", chunk.chunk_type(), chunk.length()));
+            ui.selectable_value(&mut self.hex, false, "Text");
+            ui.selectable_value(&mut self.hex, true, "Hex");
+        });
+        for line in describe_chunk(chunk) {
+            ui.label(line);
+        }
+        let data = &chunk.data()[..chunk.data().len().min(MAX_SHOWN_BYTES)];
+        let mut shown = if self.hex {
+            hexdump(data)
+        } else {
+            String::from_utf8_lossy(data).into_owned()
+        };
+        if data.len() < chunk.data().len() {
+            shown += &format!("\n… {} more bytes", chunk.data().len() - data.len());
+        }
+        egui::ScrollArea::vertical()
+            .id_salt("chunk data")
+            .max_height(200.0)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut shown.as_str())
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+    }
+}
@@ -0,0 +1,197 @@
+//! An open file, its edits and their undo history
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+use pngme_lib::{Pixels, Png};
+
+use crate::analysis::Analysis;
+use crate::browser::Browser;
+use crate::worker::Worker;
+
+/// Edits kept for undo per file, since every step holds a copy of the PNG
+const UNDO_LIMIT: usize = 100;
+
+/// A PNG file open in its own tab. Edits change the PNG in memory until it is saved.
+pub struct Document {
+    path: PathBuf,
+    png: Png,
+    browser: Browser,
+    analysis: Analysis,
+    /// Identifies the current state of `png`, so undoing back to the saved state makes
+    /// the document clean again
+    revision: u64,
+    saved_revision: u64,
+    next_revision: u64,
+    undo: Vec<(Png, u64)>,
+    redo: Vec<(Png, u64)>,
+}
+
+impl Document {
+    pub fn new(path: PathBuf, png: Png, browser: Browser) -> Self {
+        Self {
+            path,
+            png,
+            browser,
+            analysis: Analysis::default(),
+            revision: 0,
+            saved_revision: 0,
+            next_revision: 1,
+            undo: vec![],
+            redo: vec![],
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn png(&self) -> &Png {
+        &self.png
+    }
+
+    /// Draws the preview and chunk table of the PNG
+    pub fn show_browser(&mut self, ui: &mut egui::Ui) {
+        self.browser.show(ui, &self.png);
+    }
+
+    /// Draws the pixel views of the PNG, decoding it with `worker`. `payload` is the size
+    /// of the message about to be encoded.
+    pub fn show_analysis(&mut self, ui: &mut egui::Ui, worker: &mut Worker, payload: usize) {
+        self.analysis
+            .show(ui, worker, &self.path, &self.png, self.revision, payload);
+    }
+
+    /// Takes the pixels the worker decoded from `revision` of the PNG
+    pub fn decoded(&mut self, revision: u64, pixels: Result<Pixels, String>) {
+        self.analysis.decoded(revision, pixels);
+    }
+
+    /// Takes the cover image the worker decoded from `path`
+    pub fn cover_decoded(&mut self, path: PathBuf, pixels: Result<Pixels, String>) {
+        self.analysis.cover_decoded(path, pixels);
+    }
+
+    /// The file name shown on the tab
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .unwrap_or(self.path.as_os_str())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// True if the PNG has changes that were not saved
+    pub fn is_dirty(&self) -> bool {
+        self.revision != self.saved_revision
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Records that `revision` of the PNG was saved to `path`, which becomes the file's
+    /// path from now on
+    pub fn mark_saved(&mut self, path: PathBuf, revision: u64) {
+        self.path = path;
+        self.saved_revision = revision;
+    }
+
+    /// Applies `edit` to the PNG, making it undoable
+    pub fn edit(&mut self, edit: impl FnOnce(&mut Png)) {
+        let before = self.png.clone();
+        edit(&mut self.png);
+        self.undo.push((before, self.revision));
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.revision = self.next_revision;
+        self.next_revision += 1;
+        self.browser.chunks_changed(self.png.chunks().len());
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(state) = self.undo.pop() {
+            let current = self.restore(state);
+            self.redo.push(current);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(state) = self.redo.pop() {
+            let current = self.restore(state);
+            self.undo.push(current);
+        }
+    }
+
+    /// Replaces the PNG with an earlier or later state and returns the current one
+    fn restore(&mut self, (png, revision): (Png, u64)) -> (Png, u64) {
+        let current = (std::mem::replace(&mut self.png, png), self.revision);
+        self.revision = revision;
+        self.browser.chunks_changed(self.png.chunks().len());
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme_lib::{Chunk, ChunkType};
+    use std::str::FromStr;
+
+    fn document() -> Document {
+        let browser = Browser::new(&egui::Context::default(), Err(String::new()), vec![]);
+        Document::new(
+            PathBuf::from("dir/dice.png"),
+            Png::from_chunks(vec![]),
+            browser,
+        )
+    }
+
+    fn add(document: &mut Document, data: &str) {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), data.into());
+        document.edit(|png| png.append_chunk(chunk));
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut document = document();
+        assert_eq!(document.name(), "dice.png");
+        add(&mut document, "one");
+        add(&mut document, "two");
+        assert!(document.is_dirty());
+
+        document.undo();
+        assert_eq!(document.png().chunks().len(), 1);
+        document.undo();
+        assert!(!document.is_dirty());
+        assert!(!document.can_undo());
+
+        document.redo();
+        assert_eq!(document.png().chunks().len(), 1);
+        add(&mut document, "three");
+        assert!(!document.can_redo());
+    }
+
+    #[test]
+    fn test_saved_revision() {
+        let mut document = document();
+        add(&mut document, "one");
+        document.mark_saved(PathBuf::from("copy.png"), document.revision());
+        assert!(!document.is_dirty());
+        assert_eq!(document.path(), Path::new("copy.png"));
+
+        document.undo();
+        assert!(document.is_dirty());
+        document.redo();
+        assert!(!document.is_dirty());
+    }
+}
@@ -6,14 +6,29 @@
 //! Based on the [`pngme book`].
 //!
 //! [`pngme book`]: https://jrdngr.github.io/pngme_book/
+mod analysis;
+mod browser;
+mod document;
+mod worker;
+
+use std::collections::VecDeque;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use eframe::egui;
-use pngme_lib::{decode, encode, remove};
+use pngme_lib::{Chunk, ChunkType, Png};
+
+use browser::Browser;
+use document::Document;
+use worker::{Finished, Outcome, Worker};
+
+/// Chunk type filled in when the app starts
+const DEFAULT_CHUNK_TYPE: &str = "ruSt";
+
 fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([640.0, 240.0]),
+        viewport: egui::ViewportBuilder::default().with_inner_size([960.0, 600.0]),
         ..Default::default()
     };
     eframe::run_native(
@@ -23,9 +38,48 @@ fn main() -> eframe::Result {
     )
 }
 
+/// A question waiting for an answer before unsaved changes are thrown away
+enum Confirm {
+    /// Close the tab at this index
+    Close(usize),
+    Quit,
+}
+
+/// What the central panel shows for the active file
+#[derive(PartialEq)]
+enum View {
+    Chunks,
+    Pixels,
+}
+
+/// What the user asked for in the message list
+enum MessageAction {
+    Decode(usize),
+    /// Write the chunk's data to a file, for payloads that are not text
+    Export(usize),
+    Remove(usize),
+}
+
+/// A file picked with "Embed file…", encoded instead of the typed message
+struct Attachment {
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
 struct PngmeApp {
-    picked_path: Option<String>,
+    documents: Vec<Document>,
+    active: usize,
+    view: View,
+    worker: Worker,
+    /// Files waiting to be opened, since the worker runs one task at a time
+    to_open: VecDeque<PathBuf>,
+    /// The document being saved and the revision written
+    saving: Option<(usize, u64)>,
+    confirm: Option<Confirm>,
+    quit_confirmed: bool,
+    chunk_type: String,
     secret_message: String,
+    attachment: Option<Attachment>,
     decoded_message: Option<String>,
     error_message: Option<String>,
 }
@@ -33,67 +87,573 @@ struct PngmeApp {
 impl Default for PngmeApp {
     fn default() -> Self {
         Self {
-            picked_path: None,
+            documents: vec![],
+            active: 0,
+            view: View::Chunks,
+            worker: Worker::default(),
+            to_open: VecDeque::new(),
+            saving: None,
+            confirm: None,
+            quit_confirmed: false,
+            chunk_type: DEFAULT_CHUNK_TYPE.to_owned(),
             secret_message: "".to_owned(),
+            attachment: None,
             decoded_message: None,
             error_message: None,
         }
     }
 }
 
-impl eframe::App for PngmeApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.label("Select png file to encode, decode, or remove a message from it.");
-            ui.horizontal(|ui| {
-                if ui.button("Open file…").clicked() {
-                    if let Some(path) = rfd::FileDialog::new().pick_file() {
-                        self.picked_path = Some(path.display().to_string());
+/// Describes the property bits of a chunk type, as shown under the chunk type input
+fn chunk_type_flags(chunk_type: &ChunkType) -> String {
+    format!(
+        "{}, {}, {}",
+        if chunk_type.is_critical() {
+            "critical"
+        } else {
+            "ancillary"
+        },
+        if chunk_type.is_public() {
+            "public"
+        } else {
+            "private"
+        },
+        if chunk_type.is_safe_to_copy() {
+            "safe to copy"
+        } else {
+            "unsafe to copy"
+        }
+    )
+}
+
+/// A short preview of a chunk's data for the message list
+fn preview(chunk: &Chunk) -> String {
+    const MAX: usize = 40;
+    match std::str::from_utf8(chunk.data()) {
+        Ok(text) if text.chars().count() > MAX => {
+            format!("{}…", text.chars().take(MAX).collect::<String>())
+        }
+        Ok(text) => text.to_string(),
+        Err(_) => format!("{} bytes of binary data", chunk.length()),
+    }
+}
+
+/// Lists the ancillary chunks of `png`, where messages are kept, with buttons to decode
+/// or remove each one
+fn message_list(ui: &mut egui::Ui, png: &Png) -> Option<MessageAction> {
+    let messages: Vec<(usize, &Chunk)> = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| !chunk.chunk_type().is_critical())
+        .collect();
+    ui.label(format!("Messages and metadata ({}):", messages.len()));
+    let mut action = None;
+    egui::ScrollArea::vertical()
+        .max_height(200.0)
+        .show(ui, |ui| {
+            egui::Grid::new("messages").striped(true).show(ui, |ui| {
+                for (index, chunk) in &messages {
+                    ui.label(index.to_string());
+                    ui.monospace(chunk.chunk_type().to_string());
+                    ui.label(preview(chunk));
+                    if ui.button("Decode").clicked() {
+                        action = Some(MessageAction::Decode(*index));
                     }
-                }
-                if self.picked_path.is_some() && ui.button("Clear chosen file").clicked() {
-                    self.picked_path = None;
+                    if ui.button("Save…").clicked() {
+                        action = Some(MessageAction::Export(*index));
+                    }
+                    if ui.button("Remove").clicked() {
+                        action = Some(MessageAction::Remove(*index));
+                    }
+                    ui.end_row();
                 }
             });
-            if let Some(picked_path) = &self.picked_path {
-                ui.horizontal(|ui| {
-                    ui.label("Picked file:");
-                    ui.monospace(picked_path);
-                });
-                ui.label("Options:");
-                ui.horizontal(|ui| {
-                    if ui.button("Decode").clicked() {
-                        let path = PathBuf::from(picked_path.to_string());
-                        self.decoded_message = Some(decode(path, "ruSt".to_string()).unwrap());
-                    }
+        });
+    action
+}
 
-                    if ui.button("Remove").clicked() {
-                        let path = PathBuf::from(self.picked_path.as_ref().unwrap().to_string());
-                        let result = remove(path, "ruSt".to_string());
-                        if let Err(e) = result {
-                            self.error_message = Some(format!("Error: {}", e));
-                        }
-                        self.decoded_message = None;
+impl PngmeApp {
+    /// Queues `paths` to be opened in new tabs, switching to the tab of a file that is
+    /// already open instead
+    fn open(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        for path in paths {
+            if let Some(index) = self.documents.iter().position(|d| d.path() == path) {
+                self.active = index;
+            } else if !self.to_open.contains(&path) {
+                self.to_open.push_back(path);
+            }
+        }
+    }
+
+    fn pick_files(&mut self) {
+        if let Some(paths) = rfd::FileDialog::new()
+            .add_filter("PNG image", &["png"])
+            .pick_files()
+        {
+            self.open(paths);
+        }
+    }
+
+    fn document(&self) -> Option<&Document> {
+        self.documents.get(self.active)
+    }
+
+    fn document_mut(&mut self) -> Option<&mut Document> {
+        self.documents.get_mut(self.active)
+    }
+
+    /// Saves the active document to `path`, or to its own file if `path` is `None`
+    fn save(&mut self, ctx: &egui::Context, path: Option<PathBuf>) {
+        let Some(document) = self.document() else {
+            return;
+        };
+        let path = path.unwrap_or_else(|| document.path().to_path_buf());
+        let png = document.png().clone();
+        self.saving = Some((self.active, document.revision()));
+        self.worker.save(ctx, path, png);
+    }
+
+    fn save_as(&mut self, ctx: &egui::Context) {
+        let Some(document) = self.document() else {
+            return;
+        };
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("PNG image", &["png"])
+            .set_file_name(document.name());
+        if let Some(dir) = document.path().parent() {
+            dialog = dialog.set_directory(dir);
+        }
+        if let Some(path) = dialog.save_file() {
+            self.save(ctx, Some(path));
+        }
+    }
+
+    fn close(&mut self, index: usize) {
+        self.documents.remove(index);
+        if self.active > index || self.active >= self.documents.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+        self.decoded_message = None;
+    }
+
+    /// Applies the result of a finished background task
+    fn finished(&mut self, ctx: &egui::Context, finished: Finished) {
+        match finished {
+            Finished::Done(Outcome::Opened(loaded)) => {
+                let browser = Browser::new(ctx, loaded.thumbnail, loaded.bad_crcs);
+                self.documents
+                    .push(Document::new(loaded.path, loaded.png, browser));
+                self.active = self.documents.len() - 1;
+                self.decoded_message = None;
+                self.error_message = None;
+            }
+            Finished::Done(Outcome::Saved { path }) => {
+                if let Some((index, revision)) = self.saving.take() {
+                    if let Some(document) = self.documents.get_mut(index) {
+                        document.mark_saved(path, revision);
                     }
-                });
+                }
+                self.error_message = None;
+            }
+            Finished::Done(Outcome::Attached { path, data }) => {
+                self.attachment = Some(Attachment { path, data });
+                self.error_message = None;
+            }
+            Finished::Done(Outcome::Exported) => self.error_message = None,
+            Finished::Done(Outcome::Decoded {
+                document,
+                revision,
+                pixels,
+            }) => {
+                if let Some(document) = self.documents.iter_mut().find(|d| d.path() == document) {
+                    document.decoded(revision, pixels);
+                }
+            }
+            Finished::Done(Outcome::CoverDecoded {
+                document,
+                path,
+                pixels,
+            }) => {
+                if let Some(document) = self.documents.iter_mut().find(|d| d.path() == document) {
+                    document.cover_decoded(path, pixels);
+                }
+            }
+            Finished::Failed(error) => {
+                self.saving = None;
+                self.error_message = Some(error);
+            }
+            Finished::Cancelled => self.saving = None,
+        }
+    }
+
+    fn shortcuts(&mut self, ctx: &egui::Context) {
+        use egui::{Key, KeyboardShortcut, Modifiers};
+        let pressed = |modifiers, key| {
+            ctx.input_mut(|i| i.consume_shortcut(&KeyboardShortcut::new(modifiers, key)))
+        };
+        let shift = Modifiers::COMMAND | Modifiers::SHIFT;
+        // Shortcuts with shift first, since the plain ones also match with shift held
+        if pressed(shift, Key::S) {
+            self.save_as(ctx);
+        } else if pressed(Modifiers::COMMAND, Key::S) {
+            self.save(ctx, None);
+        } else if pressed(Modifiers::COMMAND, Key::O) {
+            self.pick_files();
+        } else if pressed(shift, Key::Z) || pressed(Modifiers::COMMAND, Key::Y) {
+            if let Some(document) = self.document_mut() {
+                document.redo();
+            }
+        } else if pressed(Modifiers::COMMAND, Key::Z) {
+            if let Some(document) = self.document_mut() {
+                document.undo();
+            }
+        }
+    }
+
+    /// Opens dropped files and shows where to drop while files are dragged over the window
+    fn drag_and_drop(&mut self, ctx: &egui::Context) {
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        self.open(dropped);
+
+        let hovered = ctx.input(|i| i.raw.hovered_files.len());
+        if hovered > 0 {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("drop target"),
+            ));
+            let screen = ctx.screen_rect();
+            painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(192));
+            painter.text(
+                screen.center(),
+                egui::Align2::CENTER_CENTER,
+                format!("Drop to open {} file(s)", hovered),
+                egui::TextStyle::Heading.resolve(&ctx.style()),
+                egui::Color32::WHITE,
+            );
+        }
+    }
+
+    /// Asks before quitting with unsaved changes
+    fn confirm_quit(&mut self, ctx: &egui::Context) {
+        let dirty = self.documents.iter().any(Document::is_dirty);
+        if ctx.input(|i| i.viewport().close_requested()) && dirty && !self.quit_confirmed {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.confirm = Some(Confirm::Quit);
+        }
+    }
+
+    fn confirm_window(&mut self, ctx: &egui::Context) {
+        let question = match &self.confirm {
+            Some(Confirm::Close(index)) => match self.documents.get(*index) {
+                Some(document) => format!("Discard unsaved changes to {}?", document.name()),
+                None => {
+                    self.confirm = None;
+                    return;
+                }
+            },
+            Some(Confirm::Quit) => "Quit and discard all unsaved changes?".to_string(),
+            None => return,
+        };
+        let mut answer = None;
+        egui::Window::new("Unsaved changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(question);
                 ui.horizontal(|ui| {
-                    ui.text_edit_singleline(&mut self.secret_message);
-                    if ui.button("Encode").clicked() {
-                        let path = PathBuf::from(self.picked_path.as_ref().unwrap().to_string());
-                        encode(path, "ruSt".to_string(), self.secret_message.to_string()).unwrap();
+                    if ui.button("Discard").clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        answer = Some(false);
                     }
                 });
+            });
+        match (answer, self.confirm.take()) {
+            (Some(true), Some(Confirm::Close(index))) => self.close(index),
+            (Some(true), Some(Confirm::Quit)) => {
+                self.quit_confirmed = true;
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            (None, confirm) => self.confirm = confirm,
+            _ => {}
+        }
+    }
+
+    fn tabs(&mut self, ui: &mut egui::Ui) {
+        let mut close = None;
+        ui.horizontal_wrapped(|ui| {
+            for (index, document) in self.documents.iter().enumerate() {
+                let title = if document.is_dirty() {
+                    format!("{} •", document.name())
+                } else {
+                    document.name()
+                };
+                let tab = ui
+                    .selectable_label(self.active == index, title)
+                    .on_hover_text(document.path().display().to_string());
+                if tab.clicked() && self.active != index {
+                    self.active = index;
+                    self.decoded_message = None;
+                }
+                if ui.small_button("×").on_hover_text("Close").clicked() {
+                    close = Some(index);
+                }
+                ui.separator();
+            }
+        });
+        if let Some(index) = close {
+            if self.documents[index].is_dirty() {
+                self.confirm = Some(Confirm::Close(index));
+            } else {
+                self.close(index);
+            }
+        }
+    }
+
+    fn chunk_type_input(&mut self, ui: &mut egui::Ui) -> Option<ChunkType> {
+        ui.horizontal(|ui| {
+            ui.label("Chunk type:");
+            ui.add(egui::TextEdit::singleline(&mut self.chunk_type).desired_width(60.0));
+        });
+        match ChunkType::from_str(&self.chunk_type) {
+            Ok(chunk_type) => {
+                ui.label(chunk_type_flags(&chunk_type));
+                if chunk_type.is_critical() {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "Decoders reject files with critical chunks they do not know, \
+                         use a lowercase first letter",
+                    );
+                }
+                Some(chunk_type)
+            }
+            Err(e) => {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Invalid: {}", e));
+                None
+            }
+        }
+    }
+
+    fn file_buttons(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let (open, dirty, undo, redo) = match self.document() {
+            Some(d) => (true, d.is_dirty(), d.can_undo(), d.can_redo()),
+            None => (false, false, false, false),
+        };
+        if ui.button("Open files…").clicked() {
+            self.pick_files();
+        }
+        if ui.add_enabled(dirty, egui::Button::new("Save")).clicked() {
+            self.save(ctx, None);
+        }
+        if ui
+            .add_enabled(open, egui::Button::new("Save As…"))
+            .clicked()
+        {
+            self.save_as(ctx);
+        }
+        if ui.add_enabled(undo, egui::Button::new("Undo")).clicked() {
+            if let Some(document) = self.document_mut() {
+                document.undo();
+            }
+        }
+        if ui.add_enabled(redo, egui::Button::new("Redo")).clicked() {
+            if let Some(document) = self.document_mut() {
+                document.redo();
+            }
+        }
+    }
+
+    fn message_action(&mut self, ctx: &egui::Context, action: MessageAction) {
+        match action {
+            MessageAction::Decode(index) => {
+                let Some(document) = self.document() else {
+                    return;
+                };
+                match document.png().chunks()[index].data_as_string() {
+                    Ok(message) => {
+                        self.decoded_message = Some(message);
+                        self.error_message = None;
+                    }
+                    Err(e) => self.error_message = Some(format!("Chunk {}: {}", index, e)),
+                }
+            }
+            MessageAction::Export(index) => {
+                let Some(document) = self.document() else {
+                    return;
+                };
+                let chunk = &document.png().chunks()[index];
+                let Some(path) = rfd::FileDialog::new()
+                    .set_file_name(format!("{}-{}.bin", document.name(), chunk.chunk_type()))
+                    .save_file()
+                else {
+                    return;
+                };
+                let data = chunk.data().to_vec();
+                self.worker.export(ctx, path, data);
+            }
+            MessageAction::Remove(index) => {
+                self.decoded_message = None;
+                if let Some(document) = self.document_mut() {
+                    document.edit(|png| {
+                        png.remove_chunk(index);
+                    });
+                }
+            }
+        }
+    }
+
+    /// The message to encode: typed text, or a file picked with "Embed file…"
+    fn payload_input(&mut self, ui: &mut egui::Ui) {
+        match &self.attachment {
+            Some(attachment) => {
+                let name = attachment.path.file_name().unwrap_or_default();
+                ui.label(format!(
+                    "Embedding {} ({} bytes)",
+                    name.to_string_lossy(),
+                    attachment.data.len()
+                ));
+                if ui.button("Type a message instead").clicked() {
+                    self.attachment = None;
+                }
+            }
+            None => {
+                ui.label("Message:");
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.secret_message)
+                        .desired_rows(4)
+                        .desired_width(f32::INFINITY),
+                );
+                if ui.button("Embed file…").clicked() {
+                    self.pick_attachment(ui.ctx());
+                }
+            }
+        }
+    }
+
+    /// Reads a picked file in the background, replacing the attachment once it is done
+    fn pick_attachment(&mut self, ctx: &egui::Context) {
+        if let Some(path) = rfd::FileDialog::new().pick_file() {
+            self.worker.attach(ctx, path);
+        }
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        ui.label("Open or drop PNG files to encode, decode, or remove messages.");
+        ui.horizontal(|ui| self.file_buttons(ui, ctx));
+        if self.document().is_some() {
+            ui.separator();
+            let chunk_type = self.chunk_type_input(ui);
+            self.payload_input(ui);
+            let encode = ui.add_enabled(chunk_type.is_some(), egui::Button::new("Encode"));
+            if let (true, Some(chunk_type)) = (encode.clicked(), chunk_type) {
+                let data = match &self.attachment {
+                    Some(attachment) => attachment.data.clone(),
+                    None => self.secret_message.clone().into_bytes(),
+                };
+                if data.len() > Chunk::MAX_LENGTH {
+                    self.error_message = Some(format!(
+                        "The message is larger than the {} bytes a chunk can hold",
+                        Chunk::MAX_LENGTH
+                    ));
+                } else if let Some(document) = self.document_mut() {
+                    document.edit(|png| png.append_chunk(Chunk::new(chunk_type, data)));
+                }
+            }
+            ui.label(
+                "Messages are stored as they are, so anyone with the file can read them. \
+                 pngme cannot encrypt or sign them, so encrypt or sign sensitive data with \
+                 another tool and embed the result with \"Embed file…\".",
+            );
+            ui.separator();
+            if let Some(action) = self.document().and_then(|d| message_list(ui, d.png())) {
+                self.message_action(ctx, action);
             }
+        }
+
+        if let Some(error_message) = &self.error_message {
+            ui.label("Error:");
+            ui.monospace(error_message);
+        }
+
+        if let Some(decoded_msg) = &self.decoded_message {
+            ui.label("Decoded message:");
+            ui.monospace(decoded_msg);
+        }
+    }
+}
 
-            if let Some(error_message) = &self.error_message {
-                ui.label("Error:");
-                ui.monospace(error_message);
+impl eframe::App for PngmeApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(finished) = self.worker.poll() {
+            self.finished(ctx, finished);
+        }
+        if self.worker.running().is_none() {
+            if let Some(path) = self.to_open.pop_front() {
+                self.worker.open(ctx, path);
             }
+        }
+        self.drag_and_drop(ctx);
+        self.confirm_quit(ctx);
+        let idle = self.worker.running().is_none() && self.confirm.is_none();
+        if idle {
+            self.shortcuts(ctx);
+        }
 
-            if let Some(decoded_msg) = &self.decoded_message {
-                ui.label("Decoded message:");
-                ui.monospace(decoded_msg);
+        if let Some((description, progress)) = self.worker.running() {
+            let mut cancel = false;
+            egui::TopBottomPanel::bottom("progress").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(description);
+                    ui.add(egui::ProgressBar::new(progress).desired_width(200.0));
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+            if cancel {
+                self.worker.cancel();
+                self.to_open.clear();
+            }
+        }
+        if !self.documents.is_empty() {
+            egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+                ui.add_enabled_ui(idle, |ui| self.tabs(ui));
+            });
+        }
+        egui::SidePanel::left("controls")
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.add_enabled_ui(idle, |ui| self.controls(ui, ctx));
+            });
+        let payload = match &self.attachment {
+            Some(attachment) => attachment.data.len(),
+            None => self.secret_message.len(),
+        };
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.documents.is_empty() {
+                ui.label("Open or drop a PNG file to see its chunks.");
+                return;
+            }
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.view, View::Chunks, "Chunks");
+                ui.selectable_value(&mut self.view, View::Pixels, "Pixels");
+            });
+            ui.separator();
+            let view = &self.view;
+            if let Some(document) = self.documents.get_mut(self.active) {
+                match view {
+                    View::Chunks => document.show_browser(ui),
+                    View::Pixels => document.show_analysis(ui, &mut self.worker, payload),
+                }
             }
         });
+        self.confirm_window(ctx);
     }
 }
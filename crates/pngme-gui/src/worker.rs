@@ -0,0 +1,444 @@
+//! Runs file operations and pixel decoding on a background thread so large files do not
+//! freeze the window
+use std::error::Error as _;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use eframe::egui;
+use pngme_lib::{read_png_from, validate_bytes, Chunk, Error, Pixels, Png, Problem};
+
+/// Bytes read or written between progress updates and checks for cancellation
+const BLOCK: usize = 64 * 1024;
+/// Largest width or height of the preview image, in pixels
+const THUMBNAIL_SIZE: u32 = 160;
+
+/// A file read and checked by [`Worker::open`]
+pub struct Loaded {
+    pub path: PathBuf,
+    pub png: Png,
+    pub thumbnail: Result<egui::ColorImage, String>,
+    pub bad_crcs: Vec<bool>,
+}
+
+/// What a finished task produced
+pub enum Outcome {
+    Opened(Loaded),
+    /// The PNG was written to `path`
+    Saved {
+        path: PathBuf,
+    },
+    /// The file at `path` was read to be embedded
+    Attached {
+        path: PathBuf,
+        data: Vec<u8>,
+    },
+    /// Chunk data was written to the picked file
+    Exported,
+    /// The pixels of `revision` of the document at `document`
+    Decoded {
+        document: PathBuf,
+        revision: u64,
+        pixels: Result<Pixels, String>,
+    },
+    /// The pixels of the cover image at `path`, picked for the document at `document`
+    CoverDecoded {
+        document: PathBuf,
+        path: PathBuf,
+        pixels: Result<Pixels, String>,
+    },
+}
+
+/// How a task ended, as returned by [`Worker::poll`]
+pub enum Finished {
+    Done(Outcome),
+    /// The task failed with this message
+    Failed(String),
+    Cancelled,
+}
+
+enum Message {
+    Progress(f32),
+    Finished(Finished),
+}
+
+/// Why a task stopped early
+enum Stop {
+    Failed(Error),
+    /// The task cannot be done, for the reason given
+    Refused(String),
+    Cancelled,
+}
+
+impl From<Error> for Stop {
+    fn from(error: Error) -> Self {
+        Stop::Failed(error)
+    }
+}
+
+/// Passed to running tasks to report progress and learn about cancellation
+struct Progress {
+    sender: Sender<Message>,
+    cancel: Arc<AtomicBool>,
+    ctx: egui::Context,
+}
+
+impl Progress {
+    /// Reports `fraction` of the task as done, or stops it if it was cancelled
+    fn update(&self, fraction: f32) -> Result<(), Stop> {
+        if self.cancel.load(Ordering::Relaxed) {
+            return Err(Stop::Cancelled);
+        }
+        let _ = self.sender.send(Message::Progress(fraction));
+        self.ctx.request_repaint();
+        Ok(())
+    }
+}
+
+struct Task {
+    description: String,
+    progress: f32,
+    cancel: Arc<AtomicBool>,
+    receiver: Receiver<Message>,
+}
+
+/// Runs one task at a time in the background
+#[derive(Default)]
+pub struct Worker {
+    task: Option<Task>,
+}
+
+impl Worker {
+    /// Reads, parses and checks the PNG file at `path`
+    pub fn open(&mut self, ctx: &egui::Context, path: PathBuf) {
+        let description = format!("Opening {}", path.display());
+        self.start(ctx, description, move |progress| {
+            let bytes = read_file(&path, progress)?;
+            let png = read_png_from(bytes.as_slice())?;
+            progress.update(0.6)?;
+            let mut bad_crcs = vec![false; png.chunks().len()];
+            for issue in validate_bytes(&bytes) {
+                if let (Some(index), Problem::CrcMismatch { .. }) =
+                    (issue.chunk_index, issue.problem)
+                {
+                    if let Some(bad) = bad_crcs.get_mut(index) {
+                        *bad = true;
+                    }
+                }
+            }
+            progress.update(0.7)?;
+            let thumbnail = thumbnail(&bytes);
+            Ok(Outcome::Opened(Loaded {
+                path,
+                png,
+                thumbnail,
+                bad_crcs,
+            }))
+        });
+    }
+
+    /// Writes `png` to `path`. The file is only replaced once all of it was written, so
+    /// cancelling leaves it untouched.
+    pub fn save(&mut self, ctx: &egui::Context, path: PathBuf, png: Png) {
+        let description = format!("Saving {}", path.display());
+        self.start(ctx, description, move |progress| {
+            write_file(&path, &png.as_bytes(), progress)?;
+            Ok(Outcome::Saved { path })
+        });
+    }
+
+    /// Reads the file at `path` to embed it, refusing files too large for a chunk
+    pub fn attach(&mut self, ctx: &egui::Context, path: PathBuf) {
+        let description = format!("Reading {}", path.display());
+        self.start(ctx, description, move |progress| {
+            let too_large = || {
+                Stop::Refused(format!(
+                    "{} is larger than the {} bytes a chunk can hold",
+                    path.display(),
+                    Chunk::MAX_LENGTH
+                ))
+            };
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            if size > Chunk::MAX_LENGTH as u64 {
+                return Err(too_large());
+            }
+            let data = read_file(&path, progress)?;
+            if data.len() > Chunk::MAX_LENGTH {
+                return Err(too_large());
+            }
+            Ok(Outcome::Attached { path, data })
+        });
+    }
+
+    /// Writes the chunk data `data` to `path`
+    pub fn export(&mut self, ctx: &egui::Context, path: PathBuf, data: Vec<u8>) {
+        let description = format!("Saving {}", path.display());
+        self.start(ctx, description, move |progress| {
+            write_file(&path, &data, progress)?;
+            Ok(Outcome::Exported)
+        });
+    }
+
+    /// Decodes the pixels of `revision` of the document at `document`, which holds `png`
+    pub fn decode(&mut self, ctx: &egui::Context, document: PathBuf, revision: u64, png: Png) {
+        let description = format!("Decoding the pixels of {}", document.display());
+        self.start(ctx, description, move |progress| {
+            let pixels = Pixels::decode(&png).map_err(|e| e.to_string());
+            progress.update(1.0)?;
+            Ok(Outcome::Decoded {
+                document,
+                revision,
+                pixels,
+            })
+        });
+    }
+
+    /// Reads and decodes the cover image at `path` to compare the document at `document`
+    /// with
+    pub fn decode_cover(&mut self, ctx: &egui::Context, document: PathBuf, path: PathBuf) {
+        let description = format!("Decoding {}", path.display());
+        self.start(ctx, description, move |progress| {
+            let pixels = match read_file(&path, progress) {
+                Ok(bytes) => read_png_from(bytes.as_slice())
+                    .and_then(|png| Pixels::decode(&png))
+                    .map_err(|e| error_text(&e)),
+                Err(Stop::Failed(error)) => Err(error_text(&error)),
+                Err(stop) => return Err(stop),
+            };
+            progress.update(1.0)?;
+            Ok(Outcome::CoverDecoded {
+                document,
+                path,
+                pixels,
+            })
+        });
+    }
+
+    fn start<F>(&mut self, ctx: &egui::Context, description: String, task: F)
+    where
+        F: FnOnce(&Progress) -> Result<Outcome, Stop> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let progress = Progress {
+            sender,
+            cancel: cancel.clone(),
+            ctx: ctx.clone(),
+        };
+        thread::spawn(move || {
+            let finished = match task(&progress) {
+                Ok(outcome) => Finished::Done(outcome),
+                Err(Stop::Failed(error)) => Finished::Failed(error_text(&error)),
+                Err(Stop::Refused(reason)) => Finished::Failed(reason),
+                Err(Stop::Cancelled) => Finished::Cancelled,
+            };
+            let _ = progress.sender.send(Message::Finished(finished));
+            progress.ctx.request_repaint();
+        });
+        self.task = Some(Task {
+            description,
+            progress: 0.0,
+            cancel,
+            receiver,
+        });
+    }
+
+    /// The description and progress, from 0 to 1, of the running task
+    pub fn running(&self) -> Option<(&str, f32)> {
+        self.task
+            .as_ref()
+            .map(|task| (task.description.as_str(), task.progress))
+    }
+
+    /// Asks the running task to stop at its next progress update
+    pub fn cancel(&self) {
+        if let Some(task) = &self.task {
+            task.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Collects progress from the running task and returns how it ended once it has
+    pub fn poll(&mut self) -> Option<Finished> {
+        let task = self.task.as_mut()?;
+        let finished = loop {
+            match task.receiver.try_recv() {
+                Ok(Message::Progress(progress)) => task.progress = progress,
+                Ok(Message::Finished(finished)) => break finished,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    break Finished::Failed("the background task stopped unexpectedly".into())
+                }
+            }
+        };
+        self.task = None;
+        Some(finished)
+    }
+}
+
+/// The error's message followed by the messages of its sources, which hold the details
+/// for I/O errors
+fn error_text(error: &Error) -> String {
+    let mut text = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        text += &format!(": {}", error);
+        source = error.source();
+    }
+    text
+}
+
+/// Reads the file at `path` in blocks, reporting the first half of the progress
+fn read_file(path: &Path, progress: &Progress) -> Result<Vec<u8>, Stop> {
+    let not_found = |source| Error::FileNotFound {
+        source,
+        path: path.to_path_buf(),
+    };
+    let mut file = File::open(path).map_err(not_found)?;
+    let size = file.metadata().map(|m| m.len() as usize).unwrap_or(0);
+    let mut bytes = Vec::with_capacity(size);
+    let mut block = vec![0; BLOCK];
+    loop {
+        let read = file
+            .read(&mut block)
+            .map_err(|source| Error::Read { source })?;
+        if read == 0 {
+            return Ok(bytes);
+        }
+        bytes.extend_from_slice(&block[..read]);
+        progress.update(0.5 * bytes.len() as f32 / size.max(1) as f32)?;
+    }
+}
+
+/// Writes `bytes` to a temporary file next to `path` in blocks and then moves it over
+/// `path`
+fn write_file(path: &Path, bytes: &[u8], progress: &Progress) -> Result<(), Stop> {
+    let mut temp = OsString::from(path);
+    temp.push(".pngme-tmp");
+    let temp = PathBuf::from(temp);
+    let result = (|| {
+        let write_error = |source| Error::PNGWrite { source };
+        let mut file = File::create(&temp).map_err(write_error)?;
+        for (index, block) in bytes.chunks(BLOCK).enumerate() {
+            file.write_all(block).map_err(write_error)?;
+            progress.update(((index + 1) * BLOCK).min(bytes.len()) as f32 / bytes.len() as f32)?;
+        }
+        file.sync_all().map_err(write_error)?;
+        fs::rename(&temp, path).map_err(write_error)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Decodes the image and scales it down for the preview
+fn thumbnail(bytes: &[u8]) -> Result<egui::ColorImage, String> {
+    let image = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Ok(egui::ColorImage::from_rgba_unmultiplied(
+        size,
+        image.as_raw(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme_lib::ChunkType;
+    use std::str::FromStr;
+
+    fn wait(worker: &mut Worker) -> Finished {
+        loop {
+            if let Some(finished) = worker.poll() {
+                return finished;
+            }
+            thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_save_then_open() {
+        let ctx = egui::Context::default();
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("saved.png");
+        let png = Png::from_chunks(vec![Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"hidden".to_vec(),
+        )]);
+        let mut worker = Worker::default();
+
+        worker.save(&ctx, path.clone(), png.clone());
+        assert!(worker.running().is_some());
+        assert!(matches!(
+            wait(&mut worker),
+            Finished::Done(Outcome::Saved { .. })
+        ));
+
+        worker.open(&ctx, path.clone());
+        let Finished::Done(Outcome::Opened(loaded)) = wait(&mut worker) else {
+            panic!("opening failed");
+        };
+        assert_eq!(loaded.png.as_bytes(), png.as_bytes());
+        assert!(loaded.thumbnail.is_err());
+        fs::remove_file(&path).unwrap();
+
+        worker.open(&ctx, path);
+        let Finished::Failed(error) = wait(&mut worker) else {
+            panic!("opening a missing file succeeded");
+        };
+        assert!(error.starts_with("File not found"), "{}", error);
+    }
+
+    #[test]
+    fn test_export_then_attach() {
+        let ctx = egui::Context::default();
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("exported.bin");
+        let mut worker = Worker::default();
+
+        worker.export(&ctx, path.clone(), b"payload".to_vec());
+        assert!(matches!(
+            wait(&mut worker),
+            Finished::Done(Outcome::Exported)
+        ));
+
+        worker.attach(&ctx, path.clone());
+        let Finished::Done(Outcome::Attached { data, .. }) = wait(&mut worker) else {
+            panic!("attaching failed");
+        };
+        assert_eq!(data, b"payload");
+    }
+
+    #[test]
+    fn test_decode() {
+        let ctx = egui::Context::default();
+        let dice = Path::new(env!("CARGO_MANIFEST_DIR")).join("../pngme/dice.png");
+        let mut worker = Worker::default();
+
+        worker.decode(&ctx, dice.clone(), 3, Png::from_chunks(vec![]));
+        let Finished::Done(Outcome::Decoded {
+            revision, pixels, ..
+        }) = wait(&mut worker)
+        else {
+            panic!("decoding did not finish");
+        };
+        assert_eq!(revision, 3);
+        assert!(pixels.is_err());
+
+        worker.decode_cover(&ctx, dice.clone(), dice.clone());
+        let Finished::Done(Outcome::CoverDecoded { path, pixels, .. }) = wait(&mut worker) else {
+            panic!("decoding the cover did not finish");
+        };
+        assert_eq!(path, dice);
+        assert!(pixels.is_ok());
+    }
+}
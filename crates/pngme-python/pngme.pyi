@@ -0,0 +1,116 @@
+"""Hide secret messages in PNG files"""
+
+import os
+from collections.abc import Sequence
+from typing import Protocol, final, overload
+
+from typing_extensions import Buffer, TypeAlias
+
+_StrPath: TypeAlias = str | os.PathLike[str]
+
+class _BinaryReader(Protocol):
+    def read(self) -> bytes: ...
+
+_Data: TypeAlias = Buffer | _BinaryReader
+_Source: TypeAlias = _StrPath | _Data
+
+@overload
+def encode(path: _StrPath, chunk_type: str, message: str) -> None: ...
+@overload
+def encode(path: _Data, chunk_type: str, message: str) -> bytes: ...
+def decode(path: _Source, chunk_type: str) -> str: ...
+@overload
+def remove(path: _StrPath, chunk_type: str) -> None: ...
+@overload
+def remove(path: _Data, chunk_type: str) -> bytes: ...
+@final
+class ChunkType:
+    def __init__(self, name: str) -> None: ...
+    @staticmethod
+    def from_bytes(data: bytes | bytearray | Sequence[int]) -> ChunkType: ...
+    def to_bytes(self) -> bytes: ...
+    @property
+    def is_critical(self) -> bool: ...
+    @property
+    def is_public(self) -> bool: ...
+    @property
+    def is_reserved_bit_valid(self) -> bool: ...
+    @property
+    def is_safe_to_copy(self) -> bool: ...
+    @property
+    def is_valid(self) -> bool: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+
+@final
+class Chunk:
+    def __init__(self, chunk_type: ChunkType | str, data: Buffer) -> None: ...
+    @staticmethod
+    def from_bytes(data: Buffer) -> Chunk: ...
+    def to_bytes(self) -> bytes: ...
+    @property
+    def chunk_type(self) -> ChunkType: ...
+    @property
+    def data(self) -> bytes: ...
+    @property
+    def length(self) -> int: ...
+    @property
+    def crc(self) -> int: ...
+    def data_as_string(self) -> str: ...
+    def __eq__(self, other: object) -> bool: ...
+
+@final
+class Png:
+    def __init__(self, chunks: Sequence[Chunk] = ...) -> None: ...
+    @staticmethod
+    def from_bytes(data: Buffer, check_crc: bool = False) -> Png: ...
+    @staticmethod
+    def from_file(file: _Source, check_crc: bool = False) -> Png: ...
+    def to_bytes(self) -> bytes: ...
+    def save(self, path: _StrPath) -> None: ...
+    def append(self, chunk: Chunk) -> None: ...
+    def insert(self, index: int, chunk: Chunk) -> None: ...
+    def remove(self, index: int) -> Chunk: ...
+    def chunk_by_type(self, chunk_type: ChunkType | str) -> Chunk | None: ...
+    @property
+    def chunks(self) -> list[Chunk]: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, index: int) -> Chunk: ...
+    def __delitem__(self, index: int) -> None: ...
+    def __iter__(self) -> ChunkIterator: ...
+
+@final
+class ChunkIterator:
+    def __iter__(self) -> ChunkIterator: ...
+    def __next__(self) -> Chunk: ...
+
+class PngmeError(Exception): ...
+
+class FileOpenError(PngmeError, FileNotFoundError):
+    path: str | None
+
+class ReadError(PngmeError, OSError): ...
+class WriteError(PngmeError, OSError): ...
+
+class ParseError(PngmeError, ValueError):
+    offset: int | None
+    chunk_index: int | None
+    line: int | None
+
+class CrcError(ParseError):
+    chunk_type: str | None
+
+class InvalidChunkTypeError(PngmeError, ValueError):
+    chunk_type: str | None
+
+class ChunkNotFoundError(PngmeError, ValueError):
+    chunk_type: str | None
+
+class ChunkIndexError(PngmeError, IndexError):
+    index: int | None
+    count: int | None
+
+class NotTextError(PngmeError, ValueError): ...
+
+class ManifestError(PngmeError, ValueError):
+    row: int | None
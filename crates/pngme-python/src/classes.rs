@@ -0,0 +1,315 @@
+//! Python classes wrapping [`Png`], [`Chunk`] and [`ChunkType`] for working with the
+//! structure of a PNG instead of files on disk
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use pngme_lib::{Chunk, ChunkType, Error, Png};
+
+use crate::errors::{chunk_error, into_py_err, raise, value};
+use crate::source::{parse, BytesLike, Source};
+
+/// A PNG chunk type such as `IHDR` or `ruSt`
+#[pyclass(name = "ChunkType", module = "pngme", frozen, eq, hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PyChunkType(ChunkType);
+
+#[pymethods]
+impl PyChunkType {
+    #[new]
+    fn new(name: &str) -> PyResult<Self> {
+        ChunkType::from_str(name)
+            .map(PyChunkType)
+            .map_err(|source| {
+                into_py_err(Error::InvalidChunkType {
+                    chunk_type: name.to_string(),
+                    source,
+                })
+            })
+    }
+
+    /// Creates a chunk type from its 4 bytes, which only have to be ASCII
+    #[staticmethod]
+    fn from_bytes(data: [u8; 4]) -> PyResult<Self> {
+        ChunkType::try_from(data).map(PyChunkType).map_err(|e| {
+            let chunk_type = String::from_utf8_lossy(&data).into_owned();
+            Python::with_gil(|py| {
+                raise(
+                    "InvalidChunkTypeError",
+                    e.to_string(),
+                    &[("chunk_type", value(py, chunk_type))],
+                )
+            })
+        })
+    }
+
+    /// The 4 bytes of the chunk type
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.bytes())
+    }
+
+    /// True if decoders must understand the chunk to show the image
+    #[getter]
+    fn is_critical(&self) -> bool {
+        self.0.is_critical()
+    }
+
+    /// True if the chunk type is part of the PNG spec or registered
+    #[getter]
+    fn is_public(&self) -> bool {
+        self.0.is_public()
+    }
+
+    /// True if the reserved bit, the case of the third letter, is unset
+    #[getter]
+    fn is_reserved_bit_valid(&self) -> bool {
+        self.0.is_reserved_bit_valid()
+    }
+
+    /// True if editors may copy the chunk to a modified image without understanding it
+    #[getter]
+    fn is_safe_to_copy(&self) -> bool {
+        self.0.is_safe_to_copy()
+    }
+
+    /// True if the chunk type is allowed by the PNG spec
+    #[getter]
+    fn is_valid(&self) -> bool {
+        self.0.is_valid()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ChunkType('{}')", self.0)
+    }
+}
+
+/// Either a `ChunkType` or its name, as accepted wherever a chunk type is expected
+#[derive(FromPyObject)]
+enum ChunkTypeArg {
+    Type(PyChunkType),
+    Name(String),
+}
+
+impl ChunkTypeArg {
+    fn into_chunk_type(self) -> PyResult<ChunkType> {
+        match self {
+            ChunkTypeArg::Type(chunk_type) => Ok(chunk_type.0),
+            ChunkTypeArg::Name(name) => PyChunkType::new(&name).map(|chunk_type| chunk_type.0),
+        }
+    }
+}
+
+/// A single chunk: its type and data, with the length and CRC derived from them
+#[pyclass(name = "Chunk", module = "pngme", frozen)]
+#[derive(Clone)]
+pub struct PyChunk(Chunk);
+
+#[pymethods]
+impl PyChunk {
+    #[new]
+    fn new(chunk_type: ChunkTypeArg, data: BytesLike) -> PyResult<Self> {
+        Ok(PyChunk(Chunk::new(chunk_type.into_chunk_type()?, data.0)))
+    }
+
+    /// Parses one serialized chunk: length, type, data and CRC
+    #[staticmethod]
+    fn from_bytes(data: BytesLike) -> PyResult<Self> {
+        Chunk::try_from(data.0.as_slice())
+            .map(PyChunk)
+            .map_err(|e| chunk_error(e, &data.0))
+    }
+
+    /// The chunk serialized as it is stored in a PNG file
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.as_bytes())
+    }
+
+    /// The type of the chunk
+    #[getter]
+    fn chunk_type(&self) -> PyChunkType {
+        PyChunkType(self.0.chunk_type().clone())
+    }
+
+    /// The data of the chunk
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.0.data())
+    }
+
+    /// The number of data bytes
+    #[getter]
+    fn length(&self) -> u32 {
+        self.0.length()
+    }
+
+    /// The CRC of the chunk type and data
+    #[getter]
+    fn crc(&self) -> u32 {
+        self.0.crc()
+    }
+
+    /// The data decoded as UTF-8 text
+    fn data_as_string(&self) -> PyResult<String> {
+        self.0
+            .data_as_string()
+            .map_err(|_| into_py_err(Error::StrConversion))
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.0.as_bytes() == other.0.as_bytes()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Chunk('{}', <{} bytes>)",
+            self.0.chunk_type(),
+            self.0.length()
+        )
+    }
+}
+
+/// A PNG file as a list of chunks
+///
+/// Chunks are returned as copies, so changes go through `append`, `insert` and `remove`.
+#[pyclass(name = "Png", module = "pngme")]
+#[derive(Clone)]
+pub struct PyPng(Png);
+
+#[pymethods]
+impl PyPng {
+    #[new]
+    #[pyo3(signature = (chunks = Vec::new()))]
+    fn new(chunks: Vec<PyChunk>) -> Self {
+        PyPng(Png::from_chunks(
+            chunks.into_iter().map(|chunk| chunk.0).collect(),
+        ))
+    }
+
+    /// Parses the bytes of a whole PNG file from a bytes-like object. Chunks whose CRC
+    /// does not match their data raise `CrcError` if `check_crc` is set.
+    #[staticmethod]
+    #[pyo3(signature = (data, check_crc = false))]
+    fn from_bytes(data: BytesLike, check_crc: bool) -> PyResult<Self> {
+        parse(&data.0, check_crc).map(PyPng)
+    }
+
+    /// Reads and parses the PNG file at a path or from a binary file object
+    #[staticmethod]
+    #[pyo3(signature = (file, check_crc = false))]
+    fn from_file(file: Source, check_crc: bool) -> PyResult<Self> {
+        parse(&file.bytes()?, check_crc).map(PyPng)
+    }
+
+    /// The PNG signature followed by every chunk
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.as_bytes())
+    }
+
+    /// Writes the PNG to `path`, creating or replacing the file
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        fs::write(&path, self.0.as_bytes())
+            .map_err(|source| into_py_err(Error::PNGWrite { source }))
+    }
+
+    /// Adds `chunk` as the last chunk
+    fn append(&mut self, chunk: PyChunk) {
+        self.0.append_chunk(chunk.0);
+    }
+
+    /// Adds `chunk` before the chunk at `index`. Negative indexes count from the end.
+    fn insert(&mut self, index: isize, chunk: PyChunk) {
+        let len = self.0.chunks().len() as isize;
+        let index = if index < 0 { index + len } else { index };
+        self.0.insert_chunk(index.clamp(0, len) as usize, chunk.0);
+    }
+
+    /// Removes and returns the chunk at `index`
+    fn remove(&mut self, index: isize) -> PyResult<PyChunk> {
+        let index = self.index(index)?;
+        Ok(PyChunk(self.0.remove_chunk(index)))
+    }
+
+    /// The first chunk of `chunk_type`, or `None`
+    fn chunk_by_type(&self, chunk_type: ChunkTypeArg) -> PyResult<Option<PyChunk>> {
+        let chunk_type = chunk_type.into_chunk_type()?.to_string();
+        Ok(self.0.chunk_by_type(&chunk_type).cloned().map(PyChunk))
+    }
+
+    /// A copy of every chunk, in order
+    #[getter]
+    fn chunks(&self) -> Vec<PyChunk> {
+        self.0.chunks().iter().cloned().map(PyChunk).collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.chunks().len()
+    }
+
+    fn __getitem__(&self, index: isize) -> PyResult<PyChunk> {
+        let index = self.index(index)?;
+        Ok(PyChunk(self.0.chunks()[index].clone()))
+    }
+
+    fn __delitem__(&mut self, index: isize) -> PyResult<()> {
+        self.remove(index).map(|_| ())
+    }
+
+    fn __iter__(&self) -> ChunkIterator {
+        ChunkIterator {
+            chunks: self.0.chunks().to_vec().into_iter(),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        let types: Vec<String> = self
+            .0
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        format!("Png([{}])", types.join(", "))
+    }
+}
+
+impl PyPng {
+    /// Resolves a Python index, which may be negative, to a chunk position
+    fn index(&self, index: isize) -> PyResult<usize> {
+        let len = self.0.chunks().len() as isize;
+        let resolved = if index < 0 { index + len } else { index };
+        if (0..len).contains(&resolved) {
+            Ok(resolved as usize)
+        } else {
+            Err(Python::with_gil(|py| {
+                raise(
+                    "ChunkIndexError",
+                    format!("chunk index {} out of range for {} chunks", index, len),
+                    &[("index", value(py, index)), ("count", value(py, len))],
+                )
+            }))
+        }
+    }
+}
+
+/// Iterates over copies of the chunks a `Png` had when iteration started
+#[pyclass(module = "pngme")]
+pub struct ChunkIterator {
+    chunks: std::vec::IntoIter<Chunk>,
+}
+
+#[pymethods]
+impl ChunkIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> Option<PyChunk> {
+        self.chunks.next().map(PyChunk)
+    }
+}
@@ -0,0 +1,257 @@
+//! The exceptions raised by the module. Every one derives from `PngmeError` and from the
+//! built-in exception that was raised before it existed, so existing `except ValueError`
+//! and `except FileNotFoundError` clauses keep working.
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyTuple, PyType};
+use pyo3::BoundObject;
+
+use pngme_lib::{validate_bytes, ChunkError, Error, Problem};
+
+/// Name, base classes, attributes set to `None` by default and docstring of every
+/// exception. Bases refer to built-in exceptions or to exceptions listed earlier.
+const EXCEPTIONS: &[(&str, &[&str], &[&str], &str)] = &[
+    (
+        "PngmeError",
+        &["Exception"],
+        &[],
+        "Base class of all pngme errors",
+    ),
+    (
+        "FileOpenError",
+        &["PngmeError", "FileNotFoundError"],
+        &["path"],
+        "The PNG file does not exist or cannot be opened",
+    ),
+    (
+        "ReadError",
+        &["PngmeError", "OSError"],
+        &[],
+        "The PNG could not be read",
+    ),
+    (
+        "WriteError",
+        &["PngmeError", "OSError"],
+        &[],
+        "The PNG could not be written",
+    ),
+    (
+        "ParseError",
+        &["PngmeError", "ValueError"],
+        &["offset", "chunk_index", "line"],
+        "The data is not a valid PNG, chunk, dump or image. `offset` and `chunk_index` \
+         locate the first problem in a PNG, `line` the problem in a dump.",
+    ),
+    (
+        "CrcError",
+        &["ParseError"],
+        &["chunk_type"],
+        "A chunk's stored CRC does not match its type and data",
+    ),
+    (
+        "InvalidChunkTypeError",
+        &["PngmeError", "ValueError"],
+        &["chunk_type"],
+        "The chunk type is not four ASCII letters with an uppercase third letter",
+    ),
+    (
+        "ChunkNotFoundError",
+        &["PngmeError", "ValueError"],
+        &["chunk_type"],
+        "The PNG has no chunk of the requested type",
+    ),
+    (
+        "ChunkIndexError",
+        &["PngmeError", "IndexError"],
+        &["index", "count"],
+        "There is no chunk at the requested index",
+    ),
+    (
+        "NotTextError",
+        &["PngmeError", "ValueError"],
+        &[],
+        "The chunk data is not UTF-8 text",
+    ),
+    (
+        "ManifestError",
+        &["PngmeError", "ValueError"],
+        &["row"],
+        "A bulk embedding manifest is invalid",
+    ),
+];
+
+static CLASSES: GILOnceCell<Vec<Py<PyType>>> = GILOnceCell::new();
+
+/// Creates the exception classes the first time they are needed
+fn classes(py: Python<'_>) -> PyResult<&Vec<Py<PyType>>> {
+    CLASSES.get_or_try_init(py, || {
+        let builtins = py.import("builtins")?;
+        let mut classes: Vec<Py<PyType>> = Vec::new();
+        for (name, bases, attributes, doc) in EXCEPTIONS {
+            let bases = bases
+                .iter()
+                .map(|base| match EXCEPTIONS.iter().position(|e| e.0 == *base) {
+                    Some(index) => Ok(classes[index].bind(py).clone().into_any()),
+                    None => builtins.getattr(*base),
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            let namespace = PyDict::new(py);
+            namespace.set_item("__module__", "pngme")?;
+            namespace.set_item("__doc__", *doc)?;
+            for attribute in *attributes {
+                namespace.set_item(*attribute, py.None())?;
+            }
+            let class = builtins
+                .getattr("type")?
+                .call1((*name, PyTuple::new(py, bases)?, namespace))?
+                .downcast_into::<PyType>()?;
+            classes.push(class.unbind());
+        }
+        Ok(classes)
+    })
+}
+
+/// Adds the exception classes to the module
+pub fn add_to(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    for (class, (name, ..)) in classes(module.py())?.iter().zip(EXCEPTIONS) {
+        module.add(*name, class.bind(module.py()))?;
+    }
+    Ok(())
+}
+
+/// Converts `value` for setting it as an exception attribute
+pub fn value<'py>(py: Python<'py>, value: impl IntoPyObject<'py>) -> PyObject {
+    match value.into_pyobject(py) {
+        Ok(value) => value.into_any().unbind(),
+        Err(_) => py.None(),
+    }
+}
+
+/// Creates the exception `name` with `message` and sets its attributes
+pub fn raise(name: &str, message: String, attributes: &[(&str, PyObject)]) -> PyErr {
+    Python::with_gil(|py| {
+        let create = || -> PyResult<PyErr> {
+            let index = EXCEPTIONS
+                .iter()
+                .position(|e| e.0 == name)
+                .expect("exception is listed");
+            let error = classes(py)?[index].bind(py).call1((message.clone(),))?;
+            for (attribute, value) in attributes {
+                error.setattr(*attribute, value)?;
+            }
+            Ok(PyErr::from_value(error))
+        };
+        // Creating the exception only fails if Python itself is in trouble
+        create().unwrap_or_else(|_| PyException::new_err(message.clone()))
+    })
+}
+
+/// Converts a library error into the matching exception
+pub fn into_py_err(e: Error) -> PyErr {
+    let message = e.to_string();
+    Python::with_gil(|py| match e {
+        Error::FileNotFound { path, .. } => {
+            raise("FileOpenError", message, &[("path", value(py, path))])
+        }
+        Error::Read { source } => raise("ReadError", format!("{}: {}", message, source), &[]),
+        Error::PNGWrite { source } => raise("WriteError", format!("{}: {}", message, source), &[]),
+        Error::PNGParse | Error::PixelDecode { .. } => raise("ParseError", message, &[]),
+        Error::InvalidChunk {
+            source: ChunkError::InvalidCRC,
+        } => raise("CrcError", message, &[]),
+        Error::InvalidChunk { .. } => raise("ParseError", message, &[]),
+        Error::DumpParse { line, .. } => raise("ParseError", message, &[("line", value(py, line))]),
+        Error::InvalidChunkType { chunk_type, source } => raise(
+            "InvalidChunkTypeError",
+            source.to_string(),
+            &[("chunk_type", value(py, chunk_type))],
+        ),
+        Error::ChunkNotFound { chunk_type } => raise(
+            "ChunkNotFoundError",
+            format!("No `{}` chunk found", chunk_type),
+            &[("chunk_type", value(py, chunk_type))],
+        ),
+        Error::ChunkIndexOutOfRange { index, count } => raise(
+            "ChunkIndexError",
+            message,
+            &[("index", value(py, index)), ("count", value(py, count))],
+        ),
+        Error::StrConversion => raise("NotTextError", message, &[]),
+        Error::ManifestParse { row, .. } => {
+            raise("ManifestError", message, &[("row", value(py, row))])
+        }
+        Error::InvalidRow { .. } => raise("ManifestError", message, &[]),
+    })
+}
+
+/// Converts an error from parsing `data` as a PNG, locating the first problem in it
+pub fn parse_error(e: Error, data: &[u8]) -> PyErr {
+    if !matches!(e, Error::PNGParse) {
+        return into_py_err(e);
+    }
+    // The parser recomputes CRCs, so only these problems make it fail
+    let issue = validate_bytes(data).into_iter().find(|issue| {
+        matches!(
+            issue.problem,
+            Problem::InvalidSignature | Problem::Truncated | Problem::InvalidChunkType { .. }
+        )
+    });
+    let Some(issue) = issue else {
+        return into_py_err(e);
+    };
+    Python::with_gil(|py| {
+        raise(
+            "ParseError",
+            format!("{} ({})", e, issue),
+            &[
+                ("offset", value(py, issue.offset)),
+                ("chunk_index", value(py, issue.chunk_index)),
+            ],
+        )
+    })
+}
+
+/// Fails with the first chunk in the PNG `data` whose stored CRC does not match
+pub fn check_crcs(data: &[u8]) -> PyResult<()> {
+    let issue = validate_bytes(data)
+        .into_iter()
+        .find(|issue| matches!(issue.problem, Problem::CrcMismatch { .. }));
+    let Some(issue) = issue else {
+        return Ok(());
+    };
+    Err(Python::with_gil(|py| {
+        raise(
+            "CrcError",
+            issue.to_string(),
+            &[
+                ("offset", value(py, issue.offset)),
+                ("chunk_index", value(py, issue.chunk_index)),
+                ("chunk_type", value(py, chunk_type_at(data, issue.offset))),
+            ],
+        )
+    }))
+}
+
+/// Converts an error from parsing `data` as a single chunk
+pub fn chunk_error(e: ChunkError, data: &[u8]) -> PyErr {
+    match e {
+        ChunkError::InvalidCRC => Python::with_gil(|py| {
+            raise(
+                "CrcError",
+                Error::InvalidChunk { source: e }.to_string(),
+                &[
+                    ("offset", value(py, 0)),
+                    ("chunk_type", value(py, chunk_type_at(data, 0))),
+                ],
+            )
+        }),
+        source => into_py_err(Error::InvalidChunk { source }),
+    }
+}
+
+/// The type of the chunk starting at `offset`, as far as it can be read
+fn chunk_type_at(data: &[u8], offset: usize) -> Option<String> {
+    data.get(offset + 4..offset + 8)
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+}
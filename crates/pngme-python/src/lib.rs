@@ -1,77 +1,59 @@
+mod classes;
+mod errors;
+mod source;
+
 use pyo3::prelude::*;
 
+/// Hide secret messages in PNG files
 #[pymodule]
 #[pyo3(name = "pngme")]
 mod pngme_python {
-    use pyo3::exceptions::{PyFileNotFoundError, PyIOError, PyValueError};
+    use pyo3::types::PyBytes;
     use pyo3::{prelude::*, PyResult};
-    use std::path::PathBuf;
 
-    use pngme_lib::{decode as png_decode, encode as png_encode, remove as png_remove, Error};
+    use pngme_lib::{decode_message, encode_message, remove_message};
+
+    use crate::errors::into_py_err;
+    use crate::source::Source;
+
+    #[pymodule_export]
+    use crate::classes::{ChunkIterator, PyChunk, PyChunkType, PyPng};
+
+    #[pymodule_init]
+    fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+        crate::errors::add_to(m)
+    }
 
+    /// Appends a chunk of `chunk_type` holding `message`. A path is changed in place,
+    /// while bytes, bytearrays, memoryviews and binary file objects give the new PNG as
+    /// bytes.
     #[pyfunction]
-    pub fn encode(path: PathBuf, chunk_type: String, message: String) -> PyResult<()> {
-        let result = png_encode(path, chunk_type, message);
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => match e {
-                Error::FileNotFound { source: _, path: _ } => {
-                    Err(PyFileNotFoundError::new_err(e.to_string()))
-                }
-                Error::Read { source: s } => Err(PyIOError::new_err(s.to_string())),
-                Error::PNGParse => Err(PyValueError::new_err(e.to_string())),
-                Error::InvalidChunkType {
-                    chunk_type: _,
-                    source: s,
-                } => Err(PyValueError::new_err(s.to_string())),
-                Error::PNGWrite { source: _ } => Err(PyValueError::new_err(e.to_string())),
-                Error::ChunkNotFound { chunk_type: _ } => Err(PyValueError::new_err(e.to_string())),
-                Error::StrConversion => Err(PyValueError::new_err(e.to_string())),
-            },
-        }
+    pub fn encode<'py>(
+        py: Python<'py>,
+        path: Source,
+        chunk_type: &str,
+        message: &str,
+    ) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        path.edit(py, |png| {
+            encode_message(png, chunk_type, message.as_bytes())
+        })
     }
 
+    /// Returns the message in the first chunk of `chunk_type`, from a path, bytes-like
+    /// or binary file object
     #[pyfunction]
-    pub fn decode(path: PathBuf, chunk_type: String) -> PyResult<String> {
-        let result = png_decode(path, chunk_type);
-        match result {
-            Ok(msg) => Ok(msg),
-            Err(e) => match e {
-                Error::FileNotFound { source: _, path: _ } => {
-                    Err(PyFileNotFoundError::new_err(e.to_string()))
-                }
-                Error::Read { source: _ } => Err(PyIOError::new_err(e.to_string())),
-                Error::PNGParse => Err(PyValueError::new_err(e.to_string())),
-                Error::InvalidChunkType {
-                    chunk_type: _,
-                    source: s,
-                } => Err(PyValueError::new_err(s.to_string())),
-                Error::PNGWrite { source: _ } => Err(PyValueError::new_err(e.to_string())),
-                Error::ChunkNotFound { chunk_type: _ } => Err(PyValueError::new_err(e.to_string())),
-                Error::StrConversion => Err(PyValueError::new_err(e.to_string())),
-            },
-        }
+    pub fn decode(path: Source, chunk_type: &str) -> PyResult<String> {
+        decode_message(&path.read()?, chunk_type).map_err(into_py_err)
     }
 
+    /// Removes the first chunk of `chunk_type`. A path is changed in place, while
+    /// bytes-like and binary file objects give the new PNG as bytes.
     #[pyfunction]
-    pub fn remove(path: PathBuf, chunk_type: String) -> PyResult<()> {
-        let result = png_remove(path, chunk_type);
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => match e {
-                Error::FileNotFound { source: _, path: _ } => {
-                    Err(PyFileNotFoundError::new_err(e.to_string()))
-                }
-                Error::Read { source: _ } => Err(PyIOError::new_err(e.to_string())),
-                Error::PNGParse => Err(PyValueError::new_err(e.to_string())),
-                Error::InvalidChunkType {
-                    chunk_type: _,
-                    source: s,
-                } => Err(PyValueError::new_err(s.to_string())),
-                Error::PNGWrite { source: _ } => Err(PyValueError::new_err(e.to_string())),
-                Error::ChunkNotFound { chunk_type: _ } => Err(PyValueError::new_err(e.to_string())),
-                Error::StrConversion => Err(PyValueError::new_err(e.to_string())),
-            },
-        }
+    pub fn remove<'py>(
+        py: Python<'py>,
+        path: Source,
+        chunk_type: &str,
+    ) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        path.edit(py, |png| remove_message(png, chunk_type))
     }
 }
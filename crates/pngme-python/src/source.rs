@@ -0,0 +1,108 @@
+//! Arguments that accept a PNG as a path, as bytes or as a binary file object
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use pngme_lib::{read_png_from, write_png, Error, Png};
+
+use crate::errors::{check_crcs, into_py_err, parse_error};
+
+/// The contents of any object supporting the buffer protocol with byte items, such as
+/// `bytes`, `bytearray` or `memoryview`
+pub struct BytesLike(pub Vec<u8>);
+
+impl<'py> FromPyObject<'py> for BytesLike {
+    fn extract_bound(obj: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let buffer = PyBuffer::<u8>::get(obj)?;
+        Ok(BytesLike(buffer.to_vec(obj.py())?))
+    }
+}
+
+/// A PNG given as a path, which is read and changed in place, or as data read from a
+/// bytes-like or binary file object, which is changed in memory and returned as bytes
+pub enum Source {
+    Path(PathBuf),
+    Data(Vec<u8>),
+}
+
+impl<'py> FromPyObject<'py> for Source {
+    fn extract_bound(obj: &Bound<'py, PyAny>) -> PyResult<Self> {
+        // Bytes first, since `os.fspath` would also take them as a path
+        if let Ok(BytesLike(data)) = obj.extract() {
+            return Ok(Source::Data(data));
+        }
+        if let Ok(path) = obj.extract() {
+            return Ok(Source::Path(path));
+        }
+        if obj.hasattr("read")? {
+            let BytesLike(data) = obj
+                .call_method0("read")?
+                .extract()
+                .map_err(|_| PyTypeError::new_err("file objects must be opened in binary mode"))?;
+            return Ok(Source::Data(data));
+        }
+        Err(PyTypeError::new_err(format!(
+            "expected a path, a bytes-like object or a binary file object, not {}",
+            obj.get_type().name()?
+        )))
+    }
+}
+
+impl Source {
+    /// The bytes of the PNG, read from the file for paths
+    pub fn bytes(&self) -> PyResult<Cow<'_, [u8]>> {
+        match self {
+            Source::Path(path) => {
+                let mut data = vec![];
+                File::open(path)
+                    .map_err(|source| Error::FileNotFound {
+                        source,
+                        path: path.clone(),
+                    })
+                    .and_then(|mut file| {
+                        file.read_to_end(&mut data)
+                            .map_err(|source| Error::Read { source })
+                    })
+                    .map_err(into_py_err)?;
+                Ok(Cow::Owned(data))
+            }
+            Source::Data(data) => Ok(Cow::Borrowed(data)),
+        }
+    }
+
+    /// Parses the PNG
+    pub fn read(&self) -> PyResult<Png> {
+        parse(&self.bytes()?, false)
+    }
+
+    /// Applies `edit` to the PNG. A file is written back and gives `None`, data gives
+    /// the bytes of the changed PNG.
+    pub fn edit<'py, T>(
+        &self,
+        py: Python<'py>,
+        edit: impl FnOnce(&mut Png) -> Result<T, Error>,
+    ) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        let mut png = self.read()?;
+        edit(&mut png).map_err(into_py_err)?;
+        match self {
+            Source::Path(path) => write_png(path, &png).map(|()| None).map_err(into_py_err),
+            Source::Data(_) => Ok(Some(PyBytes::new(py, &png.as_bytes()))),
+        }
+    }
+}
+
+/// Parses a whole PNG file, locating the first problem if it is invalid. Chunks with
+/// a CRC that does not match are only rejected if `check_crc` is set.
+pub fn parse(data: &[u8], check_crc: bool) -> PyResult<Png> {
+    let png = read_png_from(data).map_err(|e| parse_error(e, data))?;
+    if check_crc {
+        check_crcs(data)?;
+    }
+    Ok(png)
+}
@@ -1,3 +1,5 @@
+import io
+
 import pngme
 
 import pytest
@@ -20,4 +22,123 @@ def test_pngme_bad_chunk_type():
     file_location = "./crates/pngme-python/tests/dice.png"
     with pytest.raises(ValueError) as exc:
         pngme.encode(file_location, "bad", "some message")
-    assert "chunk type must be 4 bytes long" in str(exc.value)
\ No newline at end of file
+    assert "chunk type must be 4 bytes long" in str(exc.value)
+DICE = "./crates/pngme-python/tests/dice.png"
+
+def test_chunk_type():
+    chunk_type = pngme.ChunkType("ruSt")
+    assert str(chunk_type) == "ruSt"
+    assert repr(chunk_type) == "ChunkType('ruSt')"
+    assert chunk_type.to_bytes() == b"ruSt"
+    assert not chunk_type.is_critical
+    assert not chunk_type.is_public
+    assert chunk_type.is_reserved_bit_valid
+    assert chunk_type.is_safe_to_copy
+    assert chunk_type == pngme.ChunkType.from_bytes(b"ruSt")
+    assert len({chunk_type, pngme.ChunkType("ruSt")}) == 1
+    with pytest.raises(ValueError):
+        pngme.ChunkType("rust")
+
+def test_chunk():
+    chunk = pngme.Chunk("ruSt", b"hidden")
+    assert chunk.chunk_type == pngme.ChunkType("ruSt")
+    assert chunk.data == b"hidden"
+    assert chunk.length == 6
+    assert chunk.data_as_string() == "hidden"
+    assert pngme.Chunk.from_bytes(chunk.to_bytes()) == chunk
+    assert pngme.Chunk(pngme.ChunkType("ruSt"), b"hidden") == chunk
+    with pytest.raises(ValueError):
+        pngme.Chunk.from_bytes(chunk.to_bytes()[:-1])
+
+def test_png_structure():
+    png = pngme.Png.from_file(DICE)
+    count = len(png)
+    assert png[0].chunk_type == pngme.ChunkType("IHDR")
+    assert [chunk.chunk_type for chunk in png] == [chunk.chunk_type for chunk in png.chunks]
+
+    png.insert(1, pngme.Chunk("ruSt", b"first"))
+    png.append(pngme.Chunk("ruSt", b"last"))
+    assert len(png) == count + 2
+    assert png[1].data == b"first"
+    assert png.chunk_by_type("ruSt").data == b"first"
+
+    copy = pngme.Png.from_bytes(png.to_bytes())
+    assert copy.remove(-1).data == b"last"
+    del copy[1]
+    assert copy.to_bytes() == pngme.Png.from_file(DICE).to_bytes()
+    with pytest.raises(IndexError):
+        copy[len(copy)]
+    with pytest.raises(ValueError):
+        pngme.Png.from_bytes(b"not a png")
+
+def test_png_save(tmp_path):
+    path = tmp_path / "built.png"
+    pngme.Png([pngme.Chunk("IHDR", bytes(13)), pngme.Chunk("IEND", b"")]).save(path)
+    assert len(pngme.Png.from_file(path)) == 2
+
+def test_pngme_in_memory():
+    with open(DICE, "rb") as file:
+        original = file.read()
+    encoded = pngme.encode(original, "ruSt", "in memory")
+    assert isinstance(encoded, bytes)
+    assert pngme.decode(encoded, "ruSt") == "in memory"
+    assert pngme.decode(bytearray(encoded), "ruSt") == "in memory"
+    assert pngme.decode(memoryview(encoded), "ruSt") == "in memory"
+    assert pngme.decode(io.BytesIO(encoded), "ruSt") == "in memory"
+    assert pngme.remove(io.BytesIO(encoded), "ruSt") == original
+    with open(DICE, "rb") as file:
+        assert pngme.decode(file, "ruSt") == "No secret message found"
+    assert pngme.Png.from_bytes(bytearray(original)).to_bytes() == original
+    assert pngme.Png.from_file(io.BytesIO(original)).to_bytes() == original
+
+def test_pngme_rejects_text_files():
+    with open(DICE, encoding="latin-1") as file:
+        with pytest.raises(TypeError) as exc:
+            pngme.decode(file, "ruSt")
+    assert "binary mode" in str(exc.value)
+    with pytest.raises(TypeError):
+        pngme.decode(42, "ruSt")
+
+def test_exception_hierarchy():
+    with pytest.raises(pngme.FileOpenError) as exc:
+        pngme.decode("unknown.png", "ruSt")
+    assert isinstance(exc.value, FileNotFoundError)
+    assert isinstance(exc.value, pngme.PngmeError)
+    assert exc.value.path == "unknown.png"
+
+    with pytest.raises(pngme.InvalidChunkTypeError) as exc:
+        pngme.Chunk("rust", b"")
+    assert exc.value.chunk_type == "rust"
+    assert isinstance(exc.value, ValueError)
+
+    with pytest.raises(pngme.ChunkNotFoundError) as exc:
+        pngme.remove(pngme.Png.from_file(DICE).to_bytes(), "ruSt")
+    assert exc.value.chunk_type == "ruSt"
+
+    with pytest.raises(pngme.ChunkIndexError) as exc:
+        pngme.Png()[0]
+    assert isinstance(exc.value, IndexError)
+    assert (exc.value.index, exc.value.count) == (0, 0)
+
+    with pytest.raises(pngme.NotTextError):
+        pngme.Chunk("ruSt", b"\xff").data_as_string()
+
+def test_parse_errors_locate_problem():
+    data = bytearray(pngme.Png.from_file(DICE).to_bytes())
+    data[8 + 8 + 13] ^= 0xFF  # IHDR CRC
+    assert len(pngme.Png.from_bytes(data)) == len(pngme.Png.from_file(DICE))
+    with pytest.raises(pngme.CrcError) as exc:
+        pngme.Png.from_bytes(data, check_crc=True)
+    assert isinstance(exc.value, pngme.ParseError)
+    assert (exc.value.offset, exc.value.chunk_index, exc.value.chunk_type) == (8, 0, "IHDR")
+
+    with pytest.raises(pngme.ParseError) as exc:
+        pngme.decode(b"not a png", "ruSt")
+    assert exc.value.offset == 0
+    assert "missing PNG signature" in str(exc.value)
+
+    chunk = bytearray(pngme.Chunk("ruSt", b"data").to_bytes())
+    chunk[-1] ^= 0xFF
+    with pytest.raises(pngme.CrcError) as exc:
+        pngme.Chunk.from_bytes(chunk)
+    assert exc.value.chunk_type == "ruSt"
@@ -0,0 +1,88 @@
+import ast
+import inspect
+from pathlib import Path
+
+import pngme
+
+STUB = Path(__file__).parent.parent / "pngme.pyi"
+
+
+def stub_tree():
+    return ast.parse(STUB.read_text())
+
+
+def public(name):
+    return not name.startswith("_")
+
+
+def stub_definitions(body):
+    """Functions and classes of a module or class body by name, keeping the first overload"""
+    definitions = {}
+    for node in body:
+        if isinstance(node, (ast.FunctionDef, ast.ClassDef)):
+            definitions.setdefault(node.name, node)
+    return definitions
+
+
+def stub_parameters(function):
+    arguments = function.args
+    names = [a.arg for a in arguments.posonlyargs + arguments.args + arguments.kwonlyargs]
+    return [name for name in names if name != "self"]
+
+
+def runtime_parameters(obj):
+    return [name for name in inspect.signature(obj).parameters if name != "self"]
+
+
+def test_module_names_match():
+    stubbed = {name for name in stub_definitions(stub_tree().body) if public(name)}
+    runtime = {name for name in dir(pngme) if public(name)}
+    assert stubbed == runtime
+
+
+def test_function_signatures_match():
+    for name, node in stub_definitions(stub_tree().body).items():
+        if public(name) and isinstance(node, ast.FunctionDef):
+            assert stub_parameters(node) == runtime_parameters(getattr(pngme, name)), name
+
+
+def test_classes_match():
+    for name, node in stub_definitions(stub_tree().body).items():
+        if not public(name) or not isinstance(node, ast.ClassDef):
+            continue
+        cls = getattr(pngme, name)
+        assert [ast.unparse(base) for base in node.bases] == [
+            base.__name__ for base in cls.__bases__ if base is not object
+        ], name
+        members = stub_definitions(node.body)
+        if issubclass(cls, BaseException):
+            attributes = {
+                item.target.id for item in node.body if isinstance(item, ast.AnnAssign)
+            }
+            assert attributes == {attr for attr in vars(cls) if public(attr)}, name
+            continue
+        assert {m for m in members if public(m)} == {
+            m for m in vars(cls) if public(m)
+        }, name
+        for member, function in members.items():
+            if not public(member) or any(
+                ast.unparse(d) == "property" for d in function.decorator_list
+            ):
+                continue
+            runtime = runtime_parameters(getattr(cls, member))
+            assert stub_parameters(function) == runtime, f"{name}.{member}"
+        if "__init__" in members:
+            assert stub_parameters(members["__init__"]) == runtime_parameters(cls), name
+
+
+def test_everything_has_a_docstring():
+    assert pngme.__doc__
+    for name in dir(pngme):
+        if not public(name):
+            continue
+        obj = getattr(pngme, name)
+        assert obj.__doc__, name
+        if isinstance(obj, type) and not issubclass(obj, BaseException):
+            for member in vars(obj):
+                if public(member):
+                    assert getattr(obj, member).__doc__, f"{name}.{member}"
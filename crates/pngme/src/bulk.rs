@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crc::{Crc, CRC_64_ECMA_182};
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+
+use crate::{
+    encode_message, read_png, write_png_to, Error, FileNotFoundSnafu, PNGWriteSnafu, ReadSnafu,
+};
+
+/// Hashes payloads for the result log, so a changed message is not mistaken for a
+/// completed row
+const PAYLOAD_HASH: Crc<u64> = Crc::<u64>::new(&CRC_64_ECMA_182);
+
+/// One row of a bulk embedding manifest. Exactly one of `message` and `payload_file`
+/// must be set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestRow {
+    /// PNG file to read
+    pub input: PathBuf,
+    /// Where to write the PNG with the embedded chunk
+    pub output: PathBuf,
+    /// Type of the chunk to embed
+    pub chunk_type: String,
+    /// Message to embed
+    #[serde(default)]
+    pub message: Option<String>,
+    /// File whose contents are embedded
+    #[serde(default)]
+    pub payload_file: Option<PathBuf>,
+    /// Encryption key. Encryption is not supported, so rows with a key always fail
+    /// rather than embedding the message in the clear.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Line of the manifest the row starts on, counting from 1. Errors, results and the
+    /// result log refer to the row by this number.
+    #[serde(skip)]
+    pub line: usize,
+}
+
+/// The file formats a manifest can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// Comma separated values with a header row naming the [`ManifestRow`] fields
+    Csv,
+    /// One JSON object per line with the [`ManifestRow`] fields
+    Jsonl,
+}
+
+impl ManifestFormat {
+    /// Picks the format from the file extension: `.jsonl` and `.ndjson` are JSON lines,
+    /// anything else is CSV
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext)
+                if ext.eq_ignore_ascii_case("jsonl") || ext.eq_ignore_ascii_case("ndjson") =>
+            {
+                ManifestFormat::Jsonl
+            }
+            _ => ManifestFormat::Csv,
+        }
+    }
+}
+
+/// Parses the rows of a manifest, noting the line each one starts on
+pub fn parse_manifest(
+    reader: impl Read,
+    format: ManifestFormat,
+) -> Result<Vec<ManifestRow>, Error> {
+    let invalid = |line: u64, e: &dyn std::fmt::Display| Error::ManifestParse {
+        row: line as usize,
+        reason: e.to_string(),
+    };
+    let mut rows = vec![];
+    match format {
+        ManifestFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(reader);
+            let headers = reader.headers().map_err(|e| invalid(1, &e))?.clone();
+            for record in reader.records() {
+                let record =
+                    record.map_err(|e| invalid(e.position().map_or(0, |pos| pos.line()), &e))?;
+                let line = record.position().map_or(0, |pos| pos.line());
+                let mut row: ManifestRow = record
+                    .deserialize(Some(&headers))
+                    .map_err(|e| invalid(line, &e))?;
+                row.line = line as usize;
+                rows.push(row);
+            }
+        }
+        ManifestFormat::Jsonl => {
+            for (index, line) in BufReader::new(reader).lines().enumerate() {
+                let line = line.context(ReadSnafu)?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let number = index as u64 + 1;
+                let mut row: ManifestRow =
+                    serde_json::from_str(&line).map_err(|e| invalid(number, &e))?;
+                row.line = index + 1;
+                rows.push(row);
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Reads and parses the manifest at `path`, picking the format from its extension
+pub fn read_manifest(path: &Path) -> Result<Vec<ManifestRow>, Error> {
+    let file = File::open(path).context(FileNotFoundSnafu { path })?;
+    parse_manifest(file, ManifestFormat::from_path(path))
+}
+
+/// Embeds the message or payload file of `row` into its input and writes the result to
+/// its output, creating missing parent directories
+pub fn encode_row(row: &ManifestRow) -> Result<(), Error> {
+    embed(row, &row_payload(row)?)
+}
+
+/// The message or payload file contents of `row`
+fn row_payload(row: &ManifestRow) -> Result<Vec<u8>, Error> {
+    if row.key.is_some() {
+        return Err(Error::InvalidRow {
+            reason: "encryption keys are not supported".to_string(),
+        });
+    }
+    match (&row.message, &row.payload_file) {
+        (Some(message), None) => Ok(message.clone().into_bytes()),
+        (None, Some(path)) => fs::read(path).context(FileNotFoundSnafu { path }),
+        _ => Err(Error::InvalidRow {
+            reason: "exactly one of message and payload_file must be set".to_string(),
+        }),
+    }
+}
+
+/// Embeds `payload` as described by `row`
+fn embed(row: &ManifestRow, payload: &[u8]) -> Result<(), Error> {
+    let mut png_file = read_png(&row.input)?;
+    encode_message(&mut png_file, &row.chunk_type, payload)?;
+    if let Some(parent) = row.output.parent() {
+        fs::create_dir_all(parent).context(PNGWriteSnafu)?;
+    }
+    let file = File::create(&row.output).context(PNGWriteSnafu)?;
+    write_png_to(file, &png_file)
+}
+
+/// What happened to one manifest row
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    /// The chunk was embedded and the output written
+    Ok,
+    /// The row was already completed by an earlier run
+    Skipped,
+    /// Embedding failed, see the error
+    Failed,
+}
+
+/// The outcome of one manifest row, as written to the result log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowResult {
+    /// Line of the manifest the row starts on, see [`ManifestRow::line`]
+    pub row: usize,
+    /// Input PNG file of the row
+    pub input: PathBuf,
+    /// Output PNG file of the row
+    pub output: PathBuf,
+    /// Chunk type embedded by the row
+    pub chunk_type: String,
+    /// CRC-64 of the embedded message or payload file in hex, if it could be read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_hash: Option<String>,
+    /// What happened to the row
+    pub status: RowStatus,
+    /// Why the row failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RowResult {
+    fn new(
+        row: &ManifestRow,
+        payload_hash: Option<String>,
+        result: Result<(), Error>,
+        skipped: bool,
+    ) -> Self {
+        let (status, error) = match result {
+            Ok(()) if skipped => (RowStatus::Skipped, None),
+            Ok(()) => (RowStatus::Ok, None),
+            Err(e) => (RowStatus::Failed, Some(e.to_string())),
+        };
+        RowResult {
+            row: row.line,
+            input: row.input.clone(),
+            output: row.output.clone(),
+            chunk_type: row.chunk_type.clone(),
+            payload_hash,
+            status,
+            error,
+        }
+    }
+
+    /// Whether this log entry records `row` as completed with the payload hashed to
+    /// `payload_hash`
+    fn completes(&self, row: &ManifestRow, payload_hash: &str) -> bool {
+        self.status == RowStatus::Ok
+            && self.payload_hash.as_deref() == Some(payload_hash)
+            && self.row == row.line
+            && self.input == row.input
+            && self.output == row.output
+            && self.chunk_type == row.chunk_type
+    }
+}
+
+/// The outcome of every row of a bulk run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BulkReport {
+    /// One result per manifest row, in manifest order
+    pub results: Vec<RowResult>,
+}
+
+impl BulkReport {
+    /// Number of results with the given status
+    pub fn count(&self, status: RowStatus) -> usize {
+        self.results.iter().filter(|r| r.status == status).count()
+    }
+}
+
+/// Runs [`encode_row`] for every row of `rows`, appending one JSON line per processed row
+/// to the result log at `log`. Rows the log already records as completed with the same
+/// payload are skipped, so an interrupted or partly failed run can be resumed by running
+/// it again, while rows whose message changed are embedded again.
+pub fn encode_rows(rows: &[ManifestRow], log: &Path) -> Result<BulkReport, Error> {
+    let mut logged: HashMap<usize, RowResult> = HashMap::new();
+    if let Ok(file) = File::open(log) {
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            if let Ok(entry) = serde_json::from_str::<RowResult>(&line) {
+                if entry.status == RowStatus::Ok {
+                    logged.insert(entry.row, entry);
+                }
+            }
+        }
+    }
+
+    let mut log_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .context(FileNotFoundSnafu { path: log })?;
+    let mut report = BulkReport::default();
+    for row in rows {
+        let payload = row_payload(row);
+        let hash = payload
+            .as_ref()
+            .ok()
+            .map(|payload| format!("{:016x}", PAYLOAD_HASH.checksum(payload)));
+        if let Some(hash) = &hash {
+            if logged
+                .get(&row.line)
+                .is_some_and(|entry| entry.completes(row, hash))
+            {
+                report
+                    .results
+                    .push(RowResult::new(row, Some(hash.clone()), Ok(()), true));
+                continue;
+            }
+        }
+        let result = payload.and_then(|payload| embed(row, &payload));
+        let result = RowResult::new(row, hash, result, false);
+        let line = serde_json::to_string(&result).expect("row results always serialize");
+        writeln!(log_file, "{}", line).context(PNGWriteSnafu)?;
+        report.results.push(result);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::png::Png;
+    use std::str::FromStr;
+
+    fn write_sample(path: &Path) {
+        let chunks = ["IHDR", "IDAT", "IEND"]
+            .iter()
+            .map(|t| Chunk::new(ChunkType::from_str(t).unwrap(), vec![]))
+            .collect();
+        fs::write(path, Png::from_chunks(chunks).as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let csv = "input,output,chunk_type,message,payload_file,key\n\
+                   a.png,out/a.png,ruSt,hello,,\n\
+                   b.png,out/b.png,ruSt,,secret.txt,\n";
+        let rows = parse_manifest(csv.as_bytes(), ManifestFormat::Csv).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].message.as_deref(), Some("hello"));
+        assert_eq!(rows[0].payload_file, None);
+        assert_eq!(rows[1].payload_file, Some(PathBuf::from("secret.txt")));
+        assert_eq!(rows[1].key, None);
+        assert_eq!((rows[0].line, rows[1].line), (2, 3));
+    }
+
+    #[test]
+    fn test_parse_jsonl() {
+        let jsonl = "{\"input\":\"a.png\",\"output\":\"b.png\",\"chunk_type\":\"ruSt\",\"message\":\"hi\"}\n\n\
+                     {\"input\":\"a.png\"}\n";
+        let err = parse_manifest(jsonl.as_bytes(), ManifestFormat::Jsonl).unwrap_err();
+        assert!(matches!(err, Error::ManifestParse { row: 3, .. }));
+
+        let rows = parse_manifest(
+            jsonl.lines().next().unwrap().as_bytes(),
+            ManifestFormat::Jsonl,
+        )
+        .unwrap();
+        assert_eq!(rows[0].line, 1);
+    }
+
+    #[test]
+    fn test_rows_numbered_by_line() {
+        let csv = "input,output,chunk_type,message,payload_file,key\n\n\
+                   a.png,out/a.png,ruSt,hello,,\n\
+                   b.png,out/b.png,ruSt,hello,,,extra\n";
+        let err = parse_manifest(csv.as_bytes(), ManifestFormat::Csv).unwrap_err();
+        assert!(matches!(err, Error::ManifestParse { row: 4, .. }));
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let jsonl = "\n{\"input\":\"missing.png\",\"output\":\"out.png\",\"chunk_type\":\"ruSt\",\"message\":\"hi\"}\n";
+        let rows = parse_manifest(jsonl.as_bytes(), ManifestFormat::Jsonl).unwrap();
+        let report = encode_rows(&rows, &dir.join("log.jsonl")).unwrap();
+        assert_eq!(report.results[0].row, 2);
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            ManifestFormat::from_path(Path::new("m.JSONL")),
+            ManifestFormat::Jsonl
+        );
+        assert_eq!(
+            ManifestFormat::from_path(Path::new("m.csv")),
+            ManifestFormat::Csv
+        );
+    }
+
+    #[test]
+    fn test_encode_rows_resumes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let input = dir.join("in.png");
+        write_sample(&input);
+        let row =
+            |line: usize, output: &str, message: Option<&str>, key: Option<&str>| ManifestRow {
+                line,
+                input: input.clone(),
+                output: dir.join(output),
+                chunk_type: "ruSt".to_string(),
+                message: message.map(String::from),
+                payload_file: None,
+                key: key.map(String::from),
+            };
+        let mut rows = vec![
+            row(1, "out/a.png", Some("for alice"), None),
+            row(2, "out/b.png", None, None),
+            row(3, "out/c.png", Some("for carol"), Some("hunter2")),
+        ];
+        let log = dir.join("log.jsonl");
+
+        let report = encode_rows(&rows, &log).unwrap();
+        assert_eq!(report.count(RowStatus::Ok), 1);
+        assert_eq!(report.count(RowStatus::Failed), 2);
+        let png = read_png(&dir.join("out/a.png")).unwrap();
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(),
+            "for alice"
+        );
+
+        rows[1].message = Some("for bob".to_string());
+        let report = encode_rows(&rows, &log).unwrap();
+        let statuses: Vec<_> = report.results.iter().map(|r| r.status.clone()).collect();
+        assert_eq!(
+            statuses,
+            vec![RowStatus::Skipped, RowStatus::Ok, RowStatus::Failed]
+        );
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 5);
+
+        // A changed message is embedded again rather than skipped
+        rows[0].message = Some("for alice, updated".to_string());
+        let report = encode_rows(&rows, &log).unwrap();
+        assert_eq!(report.results[0].status, RowStatus::Ok);
+        assert_eq!(report.results[1].status, RowStatus::Skipped);
+        let png = read_png(&dir.join("out/a.png")).unwrap();
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(),
+            "for alice, updated"
+        );
+    }
+}
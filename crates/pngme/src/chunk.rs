@@ -2,14 +2,22 @@ use crate::chunk_type::ChunkType;
 use crc::{Crc, CRC_32_ISO_HDLC};
 use std::fmt;
 
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use snafu::prelude::*;
 
+/// Errors that can be returned when working with a `Chunk`
 #[derive(Debug, Snafu)]
 pub enum ChunkError {
+    /// The stored CRC did not match the chunk type and data
     #[snafu(display("Invalid CRC"))]
     InvalidCRC,
+    /// The chunk type bytes were not a valid `ChunkType`
     #[snafu(display("Invalid Chunk Type"))]
     InvalidChunkType,
+    /// The number of bytes given does not match the stored data length
+    #[snafu(display("Chunk length does not match the number of bytes"))]
+    InvalidLength,
+    /// The chunk data could not be converted to a `String`
     #[snafu(display("Chunk data is not valid UTF-8"))]
     UTF8Error,
 }
@@ -21,11 +29,27 @@ pub const CASTAGNOLI: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 pub struct Chunk {
     data: Vec<u8>,
     chunk_type: ChunkType,
+    /// The CRC read from a file, when it does not match the chunk type and data
+    stored_crc: Option<u32>,
 }
 
 impl Chunk {
+    /// The largest data length the PNG specification allows, 2^31 - 1 bytes
+    pub const MAX_LENGTH: usize = (1 << 31) - 1;
+    /// Creates a new chunk of the given type holding `data`
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-        Chunk { chunk_type, data }
+        Chunk {
+            chunk_type,
+            data,
+            stored_crc: None,
+        }
+    }
+    /// Keeps `crc` as the CRC written by [`Chunk::as_bytes`], even if it does not match
+    /// the chunk type and data. Parsing a PNG uses this so that damaged chunks are
+    /// written back as they were read rather than silently repaired.
+    pub fn with_stored_crc(mut self, crc: u32) -> Self {
+        self.stored_crc = Some(crc).filter(|&crc| crc != self.crc());
+        self
     }
     /// The length of the data portion of this chunk.
     pub fn length(&self) -> u32 {
@@ -38,6 +62,11 @@ impl Chunk {
         crc_data.append(&mut d);
         CASTAGNOLI.checksum(&crc_data)
     }
+    /// The CRC written by [`Chunk::as_bytes`]. This is [`Chunk::crc`] unless the chunk
+    /// was read with a CRC that does not match, see [`Chunk::with_stored_crc`].
+    pub fn stored_crc(&self) -> u32 {
+        self.stored_crc.unwrap_or_else(|| self.crc())
+    }
     /// The `ChunkType` of this chunk
     pub fn chunk_type(&self) -> &ChunkType {
         &self.chunk_type
@@ -56,9 +85,10 @@ impl Chunk {
     /// 1. Length of the data *(4 bytes)*
     /// 2. Chunk type *(4 bytes)*
     /// 3. The data itself *(`length` bytes)*
-    /// 4. The CRC of the chunk type and data *(4 bytes)*
+    /// 4. The CRC of the chunk type and data *(4 bytes)*, or the stored CRC of a
+    ///    damaged chunk
     pub fn as_bytes(&self) -> Vec<u8> {
-        let crc = self.crc();
+        let crc = self.stored_crc();
         let length = self.data.len() as u32;
         length
             .to_be_bytes()
@@ -82,31 +112,34 @@ impl fmt::Display for Chunk {
     }
 }
 
-fn read_be_u32(input: &mut &[u8]) -> u32 {
-    let (int_bytes, rest) = input.split_at(std::mem::size_of::<u32>());
-    *input = rest;
-    u32::from_be_bytes(int_bytes.try_into().unwrap())
+/// Serializes the chunk type, data length and CRC. The data itself is not included.
+impl Serialize for Chunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Chunk", 4)?;
+        state.serialize_field("chunk_type", &self.chunk_type)?;
+        state.serialize_field("length", &self.length())?;
+        state.serialize_field("crc", &self.stored_crc())?;
+        state.serialize_field("crc_valid", &self.stored_crc.is_none())?;
+        state.end()
+    }
 }
 
+/// Parses a single chunk serialized as described by [`Chunk::as_bytes`]. The input must
+/// hold exactly one chunk and its stored CRC must match.
 impl TryFrom<&[u8]> for Chunk {
     type Error = ChunkError;
 
-    fn try_from(mut value: &[u8]) -> Result<Chunk, Self::Error> {
-        let length = read_be_u32(&mut value);
-        dbg!(length);
-        let (chunk_type_data, value) = value.split_at(std::mem::size_of::<[u8; 4]>());
-        let mut ct_array: [u8; 4] = [0; 4];
-        ct_array.clone_from_slice(chunk_type_data);
-
-        let chunk_type =
-            ChunkType::try_from(ct_array).map_err(|_| Self::Error::InvalidChunkType)?;
-        dbg!(&chunk_type);
-        // dbg!(&value);
-        dbg!(value.len());
-        let (chunk_data, mut value) = value.split_at(length as usize);
-        let data: Vec<u8> = chunk_data.into();
-        let crc = read_be_u32(&mut value);
-        let c = Chunk::new(chunk_type, data);
+    fn try_from(value: &[u8]) -> Result<Chunk, Self::Error> {
+        ensure!(value.len() >= 12, InvalidLengthSnafu);
+        let (header, rest) = value.split_at(8);
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        ensure!(rest.len() == length + 4, InvalidLengthSnafu);
+
+        let chunk_type = ChunkType::try_from([header[4], header[5], header[6], header[7]])
+            .map_err(|_| Self::Error::InvalidChunkType)?;
+        let (data, crc) = rest.split_at(length);
+        let crc = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+        let c = Chunk::new(chunk_type, data.into());
         ensure!(crc == c.crc(), InvalidCRCSnafu);
         Ok(c)
     }
@@ -221,6 +254,47 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_chunk_round_trip() {
+        let chunk = testing_chunk();
+        let bytes = chunk.as_bytes();
+        let parsed = Chunk::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(parsed.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_chunk_from_bytes_wrong_length() {
+        let bytes = testing_chunk().as_bytes();
+        assert!(matches!(
+            Chunk::try_from(&bytes[..bytes.len() - 1]),
+            Err(ChunkError::InvalidLength)
+        ));
+        assert!(matches!(
+            Chunk::try_from(&bytes[..5]),
+            Err(ChunkError::InvalidLength)
+        ));
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(matches!(
+            Chunk::try_from(longer.as_ref()),
+            Err(ChunkError::InvalidLength)
+        ));
+    }
+
+    #[test]
+    fn test_chunk_serialize() {
+        let chunk = testing_chunk();
+        assert_eq!(
+            serde_json::to_string(&chunk).unwrap(),
+            r#"{"chunk_type":"RuSt","length":42,"crc":2882656334,"crc_valid":true}"#
+        );
+        let damaged = testing_chunk().with_stored_crc(1);
+        assert_eq!(
+            serde_json::to_string(&damaged).unwrap(),
+            r#"{"chunk_type":"RuSt","length":42,"crc":1,"crc_valid":false}"#
+        );
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -1,25 +1,38 @@
 use std::fmt;
 use std::str::{from_utf8, FromStr};
 
+use serde::{Serialize, Serializer};
 use snafu::prelude::*;
 
+/// Errors that can be returned when constructing a `ChunkType`
 #[derive(Debug, Snafu)]
 pub enum ChunkTypeError {
+    /// A byte outside of the ASCII range was supplied
     #[snafu(display("non-ascii char: `{value}` supplied"))]
-    NonAsciiChar { value: u8 },
+    NonAsciiChar {
+        /// The offending byte
+        value: u8,
+    },
+    /// A string containing non-ASCII characters was supplied
     #[snafu(display("non-ascii in: `{value}`"))]
-    NonAsciiStr { value: String },
+    NonAsciiStr {
+        /// The offending string
+        value: String,
+    },
+    /// The chunk type was not exactly 4 bytes long
     #[snafu(display("chunk type must be 4 bytes long"))]
     WrongLength,
+    /// The chunk type contained non-alphabetic characters
     #[snafu(display("chunk type must be alphabetic"))]
     NonAlpha,
+    /// The third character was lowercase
     #[snafu(display("reserved bit is invalid"))]
     InvalidReservedBit,
 }
 
 /// A validated PNG chunk type. See the PNG spec for more details.
 /// <http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html>
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct ChunkType {
     data: [u8; 4],
 }
@@ -34,39 +47,34 @@ fn is_5th_bit_set(value: u8) -> bool {
 
 impl ChunkType {
     /// Returns the raw bytes contained in this chunk
-    #[allow(dead_code)]
-    fn bytes(&self) -> [u8; 4] {
+    pub fn bytes(&self) -> [u8; 4] {
         self.data
     }
-    // Returns true if the reserved byte is valid and all four bytes are represented by the characters A-Z or a-z.
+    /// Returns true if the reserved byte is valid and all four bytes are represented by the characters A-Z or a-z.
     /// Note that this chunk type should always be valid as it is validated during construction.
     pub fn is_valid(&self) -> bool {
         self.is_reserved_bit_valid()
     }
     /// Returns the property state of the first byte as described in the PNG spec
-    #[allow(dead_code)]
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         // Ancillary bit: bit 5 of first byte
         // 0 (uppercase) = critical, 1 (lowercase) = ancillary.
-        dbg!(is_5th_bit_set(self.data[0]));
         !is_5th_bit_set(self.data[0])
     }
     /// Returns the property state of the second byte as described in the PNG spec
-    #[allow(dead_code)]
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         // Private bit: bit 5 of second byte
         // 0 (uppercase) = public, 1 (lowercase) = private.
         !is_5th_bit_set(self.data[1])
     }
     /// Returns the property state of the third byte as described in the PNG spec
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         // Reserved bit: bit 5 of third byte
         // Must be 0 (uppercase) in files conforming to this version of PNG.
         !is_5th_bit_set(self.data[2])
     }
     /// Returns the property state of the fourth byte as described in the PNG spec
-    #[allow(dead_code)]
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         // Safe-to-copy bit: bit 5 of fourth byte
         // 0 (uppercase) = unsafe to copy, 1 (lowercase) = safe to copy.
         is_5th_bit_set(self.data[3])
@@ -111,6 +119,13 @@ impl fmt::Display for ChunkType {
     }
 }
 
+/// Serializes as the 4 character string, e.g. `"ruSt"`
+impl Serialize for ChunkType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,6 +215,12 @@ mod tests {
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_chunk_type_serialize() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert_eq!(serde_json::to_string(&chunk).unwrap(), "\"RuSt\"");
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::hex::{hex_row, ROW_WIDTH};
+use crate::png::Png;
+
+/// A single difference between the chunk lists of two `Png`s.
+///
+/// Chunks are paired up by type and occurrence, so the second `tEXt` chunk of the old
+/// file is compared with the second `tEXt` chunk of the new file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ChunkChange {
+    /// A chunk only present in the new file
+    Added {
+        /// The chunk type
+        chunk_type: String,
+        /// Index of the chunk in the new file
+        index: usize,
+        /// Length of the chunk data
+        length: u32,
+    },
+    /// A chunk only present in the old file
+    Removed {
+        /// The chunk type
+        chunk_type: String,
+        /// Index of the chunk in the old file
+        index: usize,
+        /// Length of the chunk data
+        length: u32,
+    },
+    /// A chunk present in both files that moved relative to the other chunks
+    Reordered {
+        /// The chunk type
+        chunk_type: String,
+        /// Index of the chunk in the old file
+        old_index: usize,
+        /// Index of the chunk in the new file
+        new_index: usize,
+    },
+    /// A chunk present in both files whose data differs
+    Modified {
+        /// The chunk type
+        chunk_type: String,
+        /// Index of the chunk in the old file
+        old_index: usize,
+        /// Index of the chunk in the new file
+        new_index: usize,
+        /// Length of the old chunk data
+        old_length: u32,
+        /// Length of the new chunk data
+        new_length: u32,
+        /// Change in data length from old to new
+        size_delta: i64,
+        /// Rows of the chunk data that differ, if requested
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        hex_diff: Vec<HexDiffRow>,
+    },
+}
+
+/// One row of chunk data that differs between two files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HexDiffRow {
+    /// Byte offset of the row within the chunk data
+    pub offset: usize,
+    /// The old bytes as hex, empty if the old data ended before this row
+    pub old: String,
+    /// The new bytes as hex, empty if the new data ended before this row
+    pub new: String,
+}
+
+/// The chunk-level differences between two `Png`s
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PngDiff {
+    /// Every change, grouped as removals, additions, reorders and modifications
+    pub changes: Vec<ChunkChange>,
+}
+
+impl PngDiff {
+    /// Returns true if both files have identical chunk lists
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Compares the chunk lists of `old` and `new`. When `include_hex` is set, modified chunks
+/// carry a row-by-row hex diff of their data.
+pub fn diff_pngs(old: &Png, new: &Png, include_hex: bool) -> PngDiff {
+    let old_chunks = old.chunks();
+    let new_chunks = new.chunks();
+
+    let mut new_by_key: HashMap<(String, usize), usize> = HashMap::new();
+    for (index, key) in occurrence_keys(new_chunks).into_iter().enumerate() {
+        new_by_key.insert(key, index);
+    }
+
+    let mut pairs: Vec<(usize, usize)> = vec![];
+    let mut changes = vec![];
+    for (index, key) in occurrence_keys(old_chunks).into_iter().enumerate() {
+        match new_by_key.get(&key) {
+            Some(&new_index) => pairs.push((index, new_index)),
+            None => changes.push(ChunkChange::Removed {
+                chunk_type: key.0,
+                index,
+                length: old_chunks[index].length(),
+            }),
+        }
+    }
+
+    let mut paired_new = vec![false; new_chunks.len()];
+    for &(_, new_index) in &pairs {
+        paired_new[new_index] = true;
+    }
+    for (index, chunk) in new_chunks.iter().enumerate() {
+        if !paired_new[index] {
+            changes.push(ChunkChange::Added {
+                chunk_type: chunk.chunk_type().to_string(),
+                index,
+                length: chunk.length(),
+            });
+        }
+    }
+
+    let in_order = longest_increasing(&pairs.iter().map(|p| p.1).collect::<Vec<_>>());
+    for (i, &(old_index, new_index)) in pairs.iter().enumerate() {
+        if !in_order[i] {
+            changes.push(ChunkChange::Reordered {
+                chunk_type: old_chunks[old_index].chunk_type().to_string(),
+                old_index,
+                new_index,
+            });
+        }
+    }
+
+    for &(old_index, new_index) in &pairs {
+        let (old_chunk, new_chunk) = (&old_chunks[old_index], &new_chunks[new_index]);
+        if old_chunk.data() != new_chunk.data() {
+            changes.push(ChunkChange::Modified {
+                chunk_type: old_chunk.chunk_type().to_string(),
+                old_index,
+                new_index,
+                old_length: old_chunk.length(),
+                new_length: new_chunk.length(),
+                size_delta: i64::from(new_chunk.length()) - i64::from(old_chunk.length()),
+                hex_diff: if include_hex {
+                    hex_diff(old_chunk.data(), new_chunk.data())
+                } else {
+                    vec![]
+                },
+            });
+        }
+    }
+
+    PngDiff { changes }
+}
+
+/// Keys every chunk by its type and how many chunks of that type came before it
+fn occurrence_keys(chunks: &[Chunk]) -> Vec<(String, usize)> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    chunks
+        .iter()
+        .map(|c| {
+            let chunk_type = c.chunk_type().to_string();
+            let count = seen.entry(chunk_type.clone()).or_default();
+            *count += 1;
+            (chunk_type, *count - 1)
+        })
+        .collect()
+}
+
+/// Marks the members of one longest strictly increasing subsequence of `values`. Paired
+/// chunks outside of it are the ones reported as reordered. Uses patience sorting, so
+/// files with thousands of `IDAT` chunks take O(n log n) time.
+fn longest_increasing(values: &[usize]) -> Vec<bool> {
+    // `tails[k]` is the index of the smallest value ending an increasing subsequence of
+    // length `k + 1` found so far
+    let mut tails: Vec<usize> = vec![];
+    let mut previous: Vec<Option<usize>> = vec![None; values.len()];
+    for (i, &value) in values.iter().enumerate() {
+        let length = tails.partition_point(|&tail| values[tail] < value);
+        previous[i] = length.checked_sub(1).map(|k| tails[k]);
+        if length == tails.len() {
+            tails.push(i);
+        } else {
+            tails[length] = i;
+        }
+    }
+
+    let mut members = vec![false; values.len()];
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        members[i] = true;
+        current = previous[i];
+    }
+    members
+}
+
+fn hex_diff(old: &[u8], new: &[u8]) -> Vec<HexDiffRow> {
+    let rows = old.len().max(new.len()).div_ceil(ROW_WIDTH);
+    (0..rows)
+        .map(|row| row * ROW_WIDTH)
+        .filter_map(|offset| {
+            let old_row = row_slice(old, offset);
+            let new_row = row_slice(new, offset);
+            (old_row != new_row).then(|| HexDiffRow {
+                offset,
+                old: hex_row(old_row),
+                new: hex_row(new_row),
+            })
+        })
+        .collect()
+}
+
+fn row_slice(data: &[u8], offset: usize) -> &[u8] {
+    let start = offset.min(data.len());
+    let end = (offset + ROW_WIDTH).min(data.len());
+    &data[start..end]
+}
+
+impl fmt::Display for PngDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        for change in &self.changes {
+            match change {
+                ChunkChange::Added {
+                    chunk_type,
+                    index,
+                    length,
+                } => writeln!(f, "+ {chunk_type} at {index} ({length} bytes)")?,
+                ChunkChange::Removed {
+                    chunk_type,
+                    index,
+                    length,
+                } => writeln!(f, "- {chunk_type} at {index} ({length} bytes)")?,
+                ChunkChange::Reordered {
+                    chunk_type,
+                    old_index,
+                    new_index,
+                } => writeln!(f, "> {chunk_type} moved {old_index} -> {new_index}")?,
+                ChunkChange::Modified {
+                    chunk_type,
+                    old_index,
+                    old_length,
+                    new_length,
+                    size_delta,
+                    hex_diff,
+                    ..
+                } => {
+                    writeln!(
+                        f,
+                        "~ {chunk_type} at {old_index} ({old_length} -> {new_length} bytes, {size_delta:+})"
+                    )?;
+                    for row in hex_diff {
+                        writeln!(f, "    {:08x}  - {}", row.offset, row.old)?;
+                        writeln!(f, "              + {}", row.new)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.into())
+    }
+
+    fn png(chunks: &[(&str, &str)]) -> Png {
+        Png::from_chunks(chunks.iter().map(|(t, d)| chunk(t, d)).collect())
+    }
+
+    #[test]
+    fn test_identical() {
+        let a = png(&[("IHDR", "h"), ("tEXt", "a"), ("IEND", "")]);
+        let diff = diff_pngs(&a, &a, false);
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No differences\n");
+    }
+
+    #[test]
+    fn test_added_and_removed() {
+        let old = png(&[("IHDR", "h"), ("tEXt", "a"), ("IEND", "")]);
+        let new = png(&[("IHDR", "h"), ("ruSt", "secret"), ("IEND", "")]);
+        let diff = diff_pngs(&old, &new, false);
+        assert_eq!(
+            diff.changes,
+            vec![
+                ChunkChange::Removed {
+                    chunk_type: "tEXt".to_string(),
+                    index: 1,
+                    length: 1
+                },
+                ChunkChange::Added {
+                    chunk_type: "ruSt".to_string(),
+                    index: 1,
+                    length: 6
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reordered() {
+        let old = png(&[("IHDR", ""), ("gAMA", ""), ("sRGB", ""), ("tEXt", "")]);
+        let new = png(&[("IHDR", ""), ("sRGB", ""), ("tEXt", ""), ("gAMA", "")]);
+        let diff = diff_pngs(&old, &new, false);
+        assert_eq!(
+            diff.changes,
+            vec![ChunkChange::Reordered {
+                chunk_type: "gAMA".to_string(),
+                old_index: 1,
+                new_index: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn test_modified_with_hex() {
+        let old = png(&[("tEXt", "0123456789abcdefXYZ")]);
+        let new = png(&[("tEXt", "0123456789abcdefXYZW")]);
+        let diff = diff_pngs(&old, &new, true);
+        match &diff.changes[..] {
+            [ChunkChange::Modified {
+                size_delta,
+                hex_diff,
+                ..
+            }] => {
+                assert_eq!(*size_delta, 1);
+                assert_eq!(
+                    hex_diff,
+                    &vec![HexDiffRow {
+                        offset: 16,
+                        old: "58 59 5a".to_string(),
+                        new: "58 59 5a 57".to_string(),
+                    }]
+                );
+            }
+            changes => panic!("unexpected changes: {changes:?}"),
+        }
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        let members = longest_increasing(&[3, 0, 1, 4, 2]);
+        assert_eq!(members, [false, true, true, false, true]);
+        assert!(longest_increasing(&[]).is_empty());
+
+        // Large inputs stay fast and keep every member when already in order
+        let sorted: Vec<usize> = (0..100_000).collect();
+        assert!(longest_increasing(&sorted).into_iter().all(|member| member));
+        let reversed: Vec<usize> = sorted.iter().rev().copied().collect();
+        let count = longest_increasing(&reversed)
+            .into_iter()
+            .filter(|&m| m)
+            .count();
+        assert_eq!(count, 1);
+    }
+}
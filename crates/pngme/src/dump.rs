@@ -0,0 +1,401 @@
+use std::str::FromStr;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::known::describe_chunk;
+use crate::png::Png;
+use crate::Error;
+
+/// Bytes of binary data written on each `hex` line
+const HEX_LINE: usize = 32;
+/// Bytes of binary data written on each `base64` line, giving 76 characters
+const BASE64_LINE: usize = 57;
+
+/// How [`dump_png`] writes chunk data that is not readable text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DataEncoding {
+    /// Lowercase hex, 32 bytes per line
+    #[default]
+    Hex,
+    /// Standard base64, 76 characters per line
+    Base64,
+}
+
+/// Writes `png` in a line based text form that [`parse_dump`] turns back into the same
+/// bytes. Every chunk starts with a `chunk` line followed by its `length` and `crc`, a
+/// comment with its flags and the decoded fields of known chunk types, and its data.
+/// Ancillary chunks holding UTF-8 text are written as a quoted `text` line, everything
+/// else as `hex` or `base64` lines. The `crc` line holds the CRC stored in the file. When
+/// that does not match the data, a `data-crc` line with the CRC of the data follows, so
+/// that the damaged chunk is rebuilt as it was.
+///
+/// ```text
+/// chunk tEXt
+/// length 11
+/// crc 0x03c58071
+/// # ancillary, public, safe to copy
+/// # keyword: Author
+/// # text: Jane
+/// text "Author\u0000Jane"
+/// ```
+pub fn dump_png(png: &Png, encoding: DataEncoding) -> String {
+    let mut out = String::from("# pngme dump\n");
+    for chunk in png.chunks() {
+        out += &chunk_header(chunk);
+        out += &dump_data(chunk, encoding);
+    }
+    out
+}
+
+/// Writes `png` in the form of [`dump_png`] without the image data, which keeps the
+/// output small and stable enough for `git diff`. `IDAT` chunks are listed with their
+/// length and CRC only, so image changes still show up.
+pub fn summarize_png(png: &Png) -> String {
+    let mut out = String::from("# pngme summary\n");
+    for chunk in png.chunks() {
+        out += &chunk_header(chunk);
+        if chunk.chunk_type().bytes() != *b"IDAT" {
+            out += &dump_data(chunk, DataEncoding::Hex);
+        }
+    }
+    out
+}
+
+/// The `chunk`, `length` and `crc` lines of a chunk followed by comments with its flags
+/// and decoded fields
+fn chunk_header(chunk: &Chunk) -> String {
+    let chunk_type = chunk.chunk_type();
+    let mut out = format!(
+        "\nchunk {}\nlength {}\ncrc {:#010x}\n",
+        chunk_type,
+        chunk.length(),
+        chunk.stored_crc()
+    );
+    if chunk.stored_crc() != chunk.crc() {
+        out += "# CRC mismatch, the stored CRC is kept while the data matches data-crc\n";
+        out += &format!("data-crc {:#010x}\n", chunk.crc());
+    }
+    out += &format!(
+        "# {}, {}, {}{}\n",
+        if chunk_type.is_critical() {
+            "critical"
+        } else {
+            "ancillary"
+        },
+        if chunk_type.is_public() {
+            "public"
+        } else {
+            "private"
+        },
+        if chunk_type.is_safe_to_copy() {
+            "safe to copy"
+        } else {
+            "unsafe to copy"
+        },
+        if chunk_type.is_reserved_bit_valid() {
+            ""
+        } else {
+            ", reserved bit set"
+        }
+    );
+    for line in describe_chunk(chunk) {
+        // Decoded text can hold line breaks that would end the comment
+        let line: String = line
+            .chars()
+            .map(|c| match c {
+                c if c.is_control() => c.escape_default().to_string(),
+                c => c.to_string(),
+            })
+            .collect();
+        out += &format!("# {}\n", line);
+    }
+    out
+}
+
+fn dump_data(chunk: &Chunk, encoding: DataEncoding) -> String {
+    let data = chunk.data();
+    if !chunk.chunk_type().is_critical() && !data.is_empty() {
+        if let Ok(text) = std::str::from_utf8(data) {
+            // Text chunks separate their fields with nulls, but data that is mostly
+            // nulls reads better as binary
+            let nulls = text.chars().filter(|&c| c == '\0').count();
+            let printable = text.chars().filter(|c| !c.is_control()).count();
+            let readable = printable > nulls * 2
+                && text
+                    .chars()
+                    .all(|c| !c.is_control() || matches!(c, '\n' | '\t' | '\0'));
+            if readable {
+                let quoted = serde_json::to_string(text).expect("strings always serialize");
+                return format!("text {}\n", quoted);
+            }
+        }
+    }
+    match encoding {
+        DataEncoding::Hex => data
+            .chunks(HEX_LINE)
+            .map(|line| {
+                let hex: String = line.iter().map(|b| format!("{:02x}", b)).collect();
+                format!("hex {}\n", hex)
+            })
+            .collect(),
+        DataEncoding::Base64 => data
+            .chunks(BASE64_LINE)
+            .map(|line| format!("base64 {}\n", BASE64.encode(line)))
+            .collect(),
+    }
+}
+
+/// A chunk being read by [`parse_dump`]
+struct PendingChunk {
+    line: usize,
+    chunk_type: ChunkType,
+    length: Option<u32>,
+    crc: Option<u32>,
+    data_crc: Option<u32>,
+    data: Vec<u8>,
+}
+
+impl PendingChunk {
+    /// Checks the data against the `length` and `crc` lines, if present. With a
+    /// `data-crc` line the data is checked against that instead and the `crc` line is
+    /// kept as the stored CRC.
+    fn finish(self) -> Result<Chunk, Error> {
+        let chunk = Chunk::new(self.chunk_type, self.data);
+        let error = |reason: String| Error::DumpParse {
+            line: self.line,
+            reason,
+        };
+        if let Some(length) = self.length.filter(|&l| l != chunk.length()) {
+            return Err(error(format!(
+                "{} chunk has {} bytes of data but its length line says {}",
+                chunk.chunk_type(),
+                chunk.length(),
+                length
+            )));
+        }
+        if let Some(data_crc) = self.data_crc {
+            let Some(crc) = self.crc else {
+                return Err(error(format!(
+                    "{} chunk has a data-crc line but no crc line",
+                    chunk.chunk_type()
+                )));
+            };
+            if data_crc != chunk.crc() {
+                return Err(error(format!(
+                    "{} chunk data has CRC {:#010x} but its data-crc line says {:#010x}, \
+                     remove the data-crc and crc lines after editing the data",
+                    chunk.chunk_type(),
+                    chunk.crc(),
+                    data_crc
+                )));
+            }
+            return Ok(chunk.with_stored_crc(crc));
+        }
+        if let Some(crc) = self.crc.filter(|&c| c != chunk.crc()) {
+            return Err(error(format!(
+                "{} chunk data has CRC {:#010x} but its crc line says {:#010x}, \
+                 update or remove the crc line after editing the data",
+                chunk.chunk_type(),
+                chunk.crc(),
+                crc
+            )));
+        }
+        Ok(chunk)
+    }
+}
+
+/// Reads the text form written by [`dump_png`]. Blank lines and lines starting with `#`
+/// are ignored. The `length` and `crc` lines are optional, but when present the data
+/// must match them, which catches accidental edits.
+pub fn parse_dump(text: &str) -> Result<Png, Error> {
+    let mut chunks = vec![];
+    let mut pending: Option<PendingChunk> = None;
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let error = |reason: String| Error::DumpParse {
+            line: number,
+            reason,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (keyword, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let value = value.trim();
+
+        if keyword == "chunk" {
+            if let Some(chunk) = pending.take() {
+                chunks.push(chunk.finish()?);
+            }
+            let chunk_type = ChunkType::from_str(value)
+                .map_err(|e| error(format!("invalid chunk type {:?}: {}", value, e)))?;
+            pending = Some(PendingChunk {
+                line: number,
+                chunk_type,
+                length: None,
+                crc: None,
+                data_crc: None,
+                data: vec![],
+            });
+            continue;
+        }
+        let Some(chunk) = pending.as_mut() else {
+            return Err(error(format!("expected a chunk line, found {:?}", keyword)));
+        };
+        match keyword {
+            "length" => {
+                let length = value
+                    .parse()
+                    .map_err(|_| error(format!("invalid length {:?}", value)))?;
+                chunk.length = Some(length);
+            }
+            "crc" | "data-crc" => {
+                let digits = value.strip_prefix("0x").unwrap_or(value);
+                let crc = u32::from_str_radix(digits, 16)
+                    .map_err(|_| error(format!("invalid {} {:?}", keyword, value)))?;
+                if keyword == "crc" {
+                    chunk.crc = Some(crc);
+                } else {
+                    chunk.data_crc = Some(crc);
+                }
+            }
+            "hex" => chunk.data.extend(parse_hex(value).map_err(error)?),
+            "base64" => chunk.data.extend(
+                BASE64
+                    .decode(value)
+                    .map_err(|e| error(format!("invalid base64: {}", e)))?,
+            ),
+            "text" => {
+                let text: String = serde_json::from_str(value)
+                    .map_err(|e| error(format!("invalid quoted text: {}", e)))?;
+                chunk.data.extend(text.into_bytes());
+            }
+            _ => return Err(error(format!("unknown line type {:?}", keyword))),
+        }
+    }
+    if let Some(chunk) = pending {
+        chunks.push(chunk.finish()?);
+    }
+    Ok(Png::from_chunks(chunks))
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u8> = value.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err("hex data has an odd number of digits".to_string());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| format!("invalid hex digits {:?}", String::from_utf8_lossy(pair)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 2, 0, 0, 0, 2, 8, 6, 0, 0, 0]),
+            chunk("tEXt", b"Comment\0two\nlines \"quoted\""),
+            chunk("IDAT", &(0..=255).collect::<Vec<u8>>()),
+            chunk("ruSt", &[0xff, 0x00, 0x10]),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let png = testing_png();
+        for encoding in [DataEncoding::Hex, DataEncoding::Base64] {
+            let text = dump_png(&png, encoding);
+            assert_eq!(parse_dump(&text).unwrap().as_bytes(), png.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_dump_format() {
+        let text = dump_png(&testing_png(), DataEncoding::Hex);
+        assert!(text.contains("chunk tEXt\nlength 26\n"));
+        assert!(text.contains("# keyword: Comment\n"));
+        assert!(text.contains("# text: two\\nlines \"quoted\"\n"));
+        assert!(text.contains("text \"Comment\\u0000two\\nlines \\\"quoted\\\"\"\n"));
+        assert!(text.contains("chunk ruSt\nlength 3\ncrc "));
+        assert!(text.contains("hex ff0010\n"));
+        let nulls = Png::from_chunks(vec![chunk("bKGD", &[0; 6])]);
+        assert!(dump_png(&nulls, DataEncoding::Hex).contains("hex 000000000000\n"));
+        assert!(text.contains("# width: 2\n"));
+    }
+
+    #[test]
+    fn test_summarize_omits_image_data() {
+        let summary = summarize_png(&testing_png());
+        assert!(summary.starts_with("# pngme summary\n"));
+        assert!(summary.contains("chunk IDAT\nlength 256\ncrc "));
+        assert!(!summary.contains("hex 000102"));
+        assert!(summary.contains("hex ff0010\n"));
+        assert!(summary.contains("hex 00000002"));
+    }
+
+    #[test]
+    fn test_round_trip_keeps_bad_crc() {
+        let mut bytes = testing_png().as_bytes();
+        // The last byte of the ruSt chunk's CRC, just before the 12 bytes of IEND
+        let at = bytes.len() - 13;
+        bytes[at] ^= 0xff;
+        let png = crate::read_png_from(bytes.as_slice()).unwrap();
+        let text = dump_png(&png, DataEncoding::Hex);
+        assert!(text.contains("# CRC mismatch"), "{}", text);
+        assert_eq!(parse_dump(&text).unwrap().as_bytes(), bytes);
+
+        let edited = text.replace("ff0010", "ff0011");
+        let err = parse_dump(&edited).unwrap_err();
+        assert!(err.to_string().contains("data-crc line"), "{}", err);
+    }
+
+    #[test]
+    fn test_edited_data_needs_new_crc() {
+        let text = dump_png(&testing_png(), DataEncoding::Hex).replace("ff0010", "ff0011");
+        let err = parse_dump(&text).unwrap_err();
+        assert!(err.to_string().contains("crc line"), "{}", err);
+
+        let without_checks: String = text
+            .lines()
+            .filter(|line| !line.starts_with("crc") && !line.starts_with("length"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let png = parse_dump(&without_checks).unwrap();
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data(),
+            [0xff, 0x00, 0x11]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            parse_dump("hex 00"),
+            Err(Error::DumpParse { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_dump("# header\nchunk ruSt\nhex 0"),
+            Err(Error::DumpParse { line: 3, .. })
+        ));
+        assert!(matches!(
+            parse_dump("chunk ruSt\nlength 2\nhex 00"),
+            Err(Error::DumpParse { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+/// Number of bytes shown on each line of hex output
+pub(crate) const ROW_WIDTH: usize = 16;
+
+/// Formats `bytes` as space separated, lowercase hex pairs
+pub(crate) fn hex_row(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Formats `bytes` as a classic hex dump: an offset, 16 hex pairs and the
+/// printable ASCII characters of each row, with `.` standing in for the rest
+///
+/// # Examples
+///
+/// ```
+/// use pngme_lib::hexdump;
+/// assert_eq!(
+///     hexdump(b"PNG\n"),
+///     "00000000  50 4e 47 0a                                      |PNG.|\n"
+/// );
+/// ```
+pub fn hexdump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(ROW_WIDTH)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!(
+                "{:08x}  {:<width$}  |{}|\n",
+                row * ROW_WIDTH,
+                hex_row(chunk),
+                ascii,
+                width = ROW_WIDTH * 3 - 1
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_row() {
+        assert_eq!(hex_row(&[0, 15, 16, 255]), "00 0f 10 ff");
+        assert_eq!(hex_row(&[]), "");
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let dump = hexdump(&[b'a'; 17]);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("|aaaaaaaaaaaaaaaa|"));
+        assert!(lines[1].starts_with("00000010  61 "));
+        assert_eq!(hexdump(&[]), "");
+    }
+}
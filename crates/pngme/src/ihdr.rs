@@ -0,0 +1,106 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+
+/// The image header stored in the `IHDR` chunk
+/// <http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Ihdr {
+    /// Image width in pixels
+    pub width: u32,
+    /// Image height in pixels
+    pub height: u32,
+    /// Bits per sample or per palette index
+    pub bit_depth: u8,
+    /// How pixels are stored, see [`Ihdr::color_type_name`]
+    pub color_type: u8,
+    /// Compression method, 0 is the only one defined
+    pub compression_method: u8,
+    /// Filter method, 0 is the only one defined
+    pub filter_method: u8,
+    /// Interlace method, 0 for none and 1 for Adam7
+    pub interlace_method: u8,
+}
+
+impl Ihdr {
+    /// Number of data bytes in an `IHDR` chunk
+    pub const LENGTH: usize = 13;
+
+    /// Reads the header from `chunk`. Returns `None` if the chunk is not an `IHDR` chunk
+    /// or does not hold exactly 13 bytes.
+    pub fn from_chunk(chunk: &Chunk) -> Option<Ihdr> {
+        if chunk.chunk_type().bytes() != *b"IHDR" {
+            return None;
+        }
+        let data: [u8; Ihdr::LENGTH] = chunk.data().try_into().ok()?;
+        Some(Ihdr {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+
+    /// The name of the color type, or `"unknown"` for values the spec does not define
+    pub fn color_type_name(&self) -> &'static str {
+        match self.color_type {
+            0 => "grayscale",
+            2 => "truecolor",
+            3 => "indexed",
+            4 => "grayscale with alpha",
+            6 => "truecolor with alpha",
+            _ => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}, {}-bit {}{}",
+            self.width,
+            self.height,
+            self.bit_depth,
+            self.color_type_name(),
+            if self.interlace_method == 1 {
+                ", interlaced"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_from_chunk() {
+        let data = vec![0, 0, 1, 0, 0, 0, 0, 200, 8, 6, 0, 0, 1];
+        let chunk = Chunk::new(ChunkType::from_str("IHDR").unwrap(), data);
+        let ihdr = Ihdr::from_chunk(&chunk).unwrap();
+        assert_eq!(ihdr.width, 256);
+        assert_eq!(ihdr.height, 200);
+        assert_eq!(
+            ihdr.to_string(),
+            "256x200, 8-bit truecolor with alpha, interlaced"
+        );
+    }
+
+    #[test]
+    fn test_from_chunk_invalid() {
+        let short = Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 4]);
+        assert!(Ihdr::from_chunk(&short).is_none());
+        let other = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0; 13]);
+        assert!(Ihdr::from_chunk(&other).is_none());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::chunk::Chunk;
+use crate::ihdr::Ihdr;
+
+/// Decodes the fields of the standard chunk types into `name: value` lines. Unknown
+/// chunk types and chunks too short for their type give no lines.
+///
+/// # Examples
+///
+/// ```
+/// use std::str::FromStr;
+/// use pngme_lib::{describe_chunk, Chunk, ChunkType};
+/// let chunk = Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0, 0, 0xb1, 0x8f]);
+/// assert_eq!(describe_chunk(&chunk), vec!["gamma: 0.45455"]);
+/// ```
+pub fn describe_chunk(chunk: &Chunk) -> Vec<String> {
+    let data = chunk.data();
+    match &chunk.chunk_type().bytes() {
+        b"IHDR" => match Ihdr::from_chunk(chunk) {
+            Some(ihdr) => vec![
+                format!("width: {}", ihdr.width),
+                format!("height: {}", ihdr.height),
+                format!("bit depth: {}", ihdr.bit_depth),
+                format!(
+                    "color type: {} ({})",
+                    ihdr.color_type,
+                    ihdr.color_type_name()
+                ),
+                format!("compression method: {}", ihdr.compression_method),
+                format!("filter method: {}", ihdr.filter_method),
+                format!("interlace method: {}", ihdr.interlace_method),
+            ],
+            None => vec![],
+        },
+        b"PLTE" => vec![format!("entries: {}", data.len() / 3)],
+        b"IDAT" => vec!["compressed image data".to_string()],
+        b"gAMA" if data.len() == 4 => {
+            vec![format!("gamma: {:.5}", be_u32(data, 0) as f64 / 100_000.0)]
+        }
+        b"cHRM" if data.len() == 32 => ["white point", "red", "green", "blue"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                format!(
+                    "{}: x {:.5}, y {:.5}",
+                    name,
+                    be_u32(data, i * 8) as f64 / 100_000.0,
+                    be_u32(data, i * 8 + 4) as f64 / 100_000.0
+                )
+            })
+            .collect(),
+        b"sRGB" if data.len() == 1 => {
+            let intent = match data[0] {
+                0 => "perceptual",
+                1 => "relative colorimetric",
+                2 => "saturation",
+                3 => "absolute colorimetric",
+                _ => "unknown",
+            };
+            vec![format!("rendering intent: {} ({})", data[0], intent)]
+        }
+        b"iCCP" => match split_keyword(data) {
+            Some((name, rest)) => vec![
+                format!("profile name: {}", name),
+                format!("compressed profile: {} bytes", rest.len().saturating_sub(1)),
+            ],
+            None => vec![],
+        },
+        b"tEXt" => match split_keyword(data) {
+            Some((keyword, text)) => vec![
+                format!("keyword: {}", keyword),
+                format!("text: {}", String::from_utf8_lossy(text)),
+            ],
+            None => vec![],
+        },
+        b"zTXt" => match split_keyword(data) {
+            Some((keyword, rest)) => vec![
+                format!("keyword: {}", keyword),
+                format!("compressed text: {} bytes", rest.len().saturating_sub(1)),
+            ],
+            None => vec![],
+        },
+        b"iTXt" => describe_itxt(data),
+        b"tIME" if data.len() == 7 => vec![format!(
+            "modified: {:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            u16::from_be_bytes([data[0], data[1]]),
+            data[2],
+            data[3],
+            data[4],
+            data[5],
+            data[6]
+        )],
+        b"pHYs" if data.len() == 9 => vec![
+            format!("pixels per unit: {} x {}", be_u32(data, 0), be_u32(data, 4)),
+            format!("unit: {}", if data[8] == 1 { "meter" } else { "unknown" }),
+        ],
+        b"bKGD" => vec![format!("background: {:?}", data)],
+        b"sBIT" => vec![format!("significant bits: {:?}", data)],
+        b"tRNS" => vec![format!("transparency: {} bytes", data.len())],
+        b"eXIf" => vec![format!("exif data: {} bytes", data.len())],
+        b"IEND" => vec!["end of image".to_string()],
+        _ => vec![],
+    }
+}
+
+fn be_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Splits the null terminated Latin-1 keyword that starts text and profile chunks from
+/// the rest of the data
+fn split_keyword(data: &[u8]) -> Option<(String, &[u8])> {
+    let end = data.iter().position(|&b| b == 0)?;
+    let keyword = data[..end].iter().map(|&b| b as char).collect();
+    Some((keyword, &data[end + 1..]))
+}
+
+fn describe_itxt(data: &[u8]) -> Vec<String> {
+    let Some((keyword, rest)) = split_keyword(data) else {
+        return vec![];
+    };
+    let [compressed, _method, rest @ ..] = rest else {
+        return vec![];
+    };
+    let mut fields = rest.splitn(3, |&b| b == 0);
+    let (Some(language), Some(translated), Some(text)) =
+        (fields.next(), fields.next(), fields.next())
+    else {
+        return vec![];
+    };
+    let mut lines = vec![format!("keyword: {}", keyword)];
+    if !language.is_empty() {
+        lines.push(format!("language: {}", String::from_utf8_lossy(language)));
+    }
+    if !translated.is_empty() {
+        lines.push(format!(
+            "translated keyword: {}",
+            String::from_utf8_lossy(translated)
+        ));
+    }
+    lines.push(if *compressed == 1 {
+        format!("compressed text: {} bytes", text.len())
+    } else {
+        format!("text: {}", String::from_utf8_lossy(text))
+    });
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn describe(chunk_type: &str, data: &[u8]) -> Vec<String> {
+        describe_chunk(&Chunk::new(
+            ChunkType::from_str(chunk_type).unwrap(),
+            data.to_vec(),
+        ))
+    }
+
+    #[test]
+    fn test_describe_text_chunks() {
+        assert_eq!(
+            describe("tEXt", b"Author\0Jane"),
+            vec!["keyword: Author", "text: Jane"]
+        );
+        assert_eq!(
+            describe("iTXt", b"Title\0\0\0en\0Titel\0Dice"),
+            vec![
+                "keyword: Title",
+                "language: en",
+                "translated keyword: Titel",
+                "text: Dice"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_describe_time() {
+        assert_eq!(
+            describe("tIME", &[0x07, 0xe8, 2, 29, 13, 5, 9]),
+            vec!["modified: 2024-02-29 13:05:09 UTC"]
+        );
+    }
+
+    #[test]
+    fn test_describe_malformed_or_unknown() {
+        assert!(describe("tIME", &[1, 2]).is_empty());
+        assert!(describe("tEXt", b"no separator").is_empty());
+        assert!(describe("ruSt", b"secret").is_empty());
+    }
+}
@@ -7,13 +7,40 @@
 //!
 //! [`pngme book`]: https://jrdngr.github.io/pngme_book/
 
+mod bulk;
 mod chunk;
 mod chunk_type;
+mod diff;
+mod dump;
+mod hex;
+mod ihdr;
+mod known;
+mod pixels;
 mod png;
+mod strip;
+mod transplant;
+mod validate;
+
+pub use bulk::{
+    encode_row, encode_rows, parse_manifest, read_manifest, BulkReport, ManifestFormat,
+    ManifestRow, RowResult, RowStatus,
+};
+pub use chunk::{Chunk, ChunkError};
+pub use chunk_type::{ChunkType, ChunkTypeError};
+pub use diff::{diff_pngs, ChunkChange, HexDiffRow, PngDiff};
+pub use dump::{dump_png, parse_dump, summarize_png, DataEncoding};
+pub use hex::hexdump;
+pub use ihdr::Ihdr;
+pub use known::describe_chunk;
+pub use pixels::Pixels;
+pub use png::{Png, PngError};
+pub use strip::{StripPolicy, StripPreset};
+pub use transplant::{copy_chunks, SkipReason, TransplantOptions, TransplantReport};
+pub use validate::{validate_bytes, Problem, ValidationIssue};
 
 use std::fs::{self, OpenOptions};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use snafu::prelude::*;
@@ -60,13 +87,78 @@ pub enum Error {
         /// The chunk type that was not found
         chunk_type: String,
     },
+    /// A serialized chunk could not be read
+    #[snafu(display("Invalid chunk: {source}"))]
+    InvalidChunk {
+        /// The source error
+        source: chunk::ChunkError,
+    },
+    /// There is no chunk at the requested position
+    #[snafu(display("No chunk at index {index}, the file has {count} chunks"))]
+    ChunkIndexOutOfRange {
+        /// The requested position
+        index: usize,
+        /// The number of chunks in the file
+        count: usize,
+    },
+    /// The text form of a PNG could not be read
+    #[snafu(display("Invalid dump at line {line}: {reason}"))]
+    DumpParse {
+        /// The line the problem was found on, starting at 1
+        line: usize,
+        /// What is wrong with the line
+        reason: String,
+    },
+    /// A bulk embedding manifest could not be parsed
+    #[snafu(display("Invalid manifest row on line {row}: {reason}"))]
+    ManifestParse {
+        /// The manifest line the row that could not be parsed starts on, starting at 1
+        row: usize,
+        /// Why the row could not be parsed
+        reason: String,
+    },
+    /// A manifest row cannot be embedded as written
+    #[snafu(display("Invalid manifest row: {reason}"))]
+    InvalidRow {
+        /// Why the row cannot be embedded
+        reason: String,
+    },
+    /// The image data in the `IDAT` chunks could not be decoded
+    #[snafu(display("Cannot decode pixels: {reason}"))]
+    PixelDecode {
+        /// What is wrong with the image data
+        reason: String,
+    },
 }
 
-fn open_png(path: &PathBuf) -> Result<png::Png, Error> {
-    let mut f = fs::File::open(path).context(FileNotFoundSnafu { path })?;
+/// Reads and parses the PNG file at `path`
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use pngme_lib::read_png;
+/// let png = read_png(Path::new("dice.png")).unwrap();
+/// println!("{} chunks", png.chunks().len());
+/// ```
+pub fn read_png(path: &Path) -> Result<png::Png, Error> {
+    let f = fs::File::open(path).context(FileNotFoundSnafu { path })?;
+    read_png_from(f)
+}
+
+/// Reads and parses a PNG from any reader, such as stdin
+///
+/// # Examples
+///
+/// ```no_run
+/// use pngme_lib::read_png_from;
+/// let png = read_png_from(std::io::stdin()).unwrap();
+/// println!("{} chunks", png.chunks().len());
+/// ```
+pub fn read_png_from(mut reader: impl Read) -> Result<png::Png, Error> {
     let mut data = vec![];
-    f.read_to_end(&mut data).context(ReadSnafu)?;
-    let (remaining, png_file) = png::parse_png(&data).unwrap();
+    reader.read_to_end(&mut data).context(ReadSnafu)?;
+    let (remaining, png_file) = png::parse_png(&data).map_err(|_| Error::PNGParse)?;
     ensure!(remaining.is_empty(), PNGParseSnafu);
     Ok(png_file)
 }
@@ -84,28 +176,48 @@ fn open_png(path: &PathBuf) -> Result<png::Png, Error> {
 /// encode(path, chunk_type, message).unwrap();
 /// ```
 pub fn encode(path: PathBuf, chunk_type: String, message: String) -> Result<(), Error> {
-    let ct = chunk_type.clone();
-    let mut png_file = open_png(&path)?;
+    let mut png_file = read_png(&path)?;
+    encode_message(&mut png_file, &chunk_type, message.as_bytes())?;
+    write_png(&path, &png_file)?;
+    Ok(())
+}
+
+/// Appends a chunk of `chunk_type` holding `message` to a PNG in memory
+///
+/// # Examples
+///
+/// ```
+/// use pngme_lib::{decode_message, encode_message, Png};
+/// let mut png = Png::from_chunks(vec![]);
+/// encode_message(&mut png, "ruSt", b"hidden").unwrap();
+/// assert_eq!(decode_message(&png, "ruSt").unwrap(), "hidden");
+/// ```
+pub fn encode_message(png: &mut png::Png, chunk_type: &str, message: &[u8]) -> Result<(), Error> {
     let chunk_type =
-        chunk_type::ChunkType::from_str(&chunk_type).map_err(|s| Error::InvalidChunkType {
-            chunk_type: ct.clone(),
+        chunk_type::ChunkType::from_str(chunk_type).map_err(|s| Error::InvalidChunkType {
+            chunk_type: chunk_type.to_string(),
             source: s,
         })?;
-
-    let secret_chunk = chunk::Chunk::new(chunk_type, message.into());
-    png_file.append_chunk(secret_chunk);
-    write_png(path, png_file)?;
+    png.append_chunk(chunk::Chunk::new(chunk_type, message.to_vec()));
     Ok(())
 }
 
-fn write_png(path: PathBuf, png_file: png::Png) -> Result<(), Error> {
+/// Writes `png_file` over the existing file at `path`
+pub fn write_png(path: &Path, png_file: &png::Png) -> Result<(), Error> {
     let mut file = OpenOptions::new()
         .write(true)
         .truncate(true)
-        .open(&path)
+        .open(path)
         .context(FileNotFoundSnafu { path })?;
-    file.write_all(&png_file.as_bytes())
+    write_png_to(&mut file, png_file)
+}
+
+/// Writes `png_file` to any writer, such as stdout
+pub fn write_png_to(mut writer: impl Write, png_file: &png::Png) -> Result<(), Error> {
+    writer
+        .write_all(&png_file.as_bytes())
         .context(PNGWriteSnafu)?;
+    writer.flush().context(PNGWriteSnafu)?;
     Ok(())
 }
 
@@ -122,13 +234,34 @@ fn write_png(path: PathBuf, png_file: png::Png) -> Result<(), Error> {
 /// assert_eq!(msg, "This is a secret message");
 /// ```
 pub fn decode(path: PathBuf, chunk_type: String) -> Result<String, Error> {
-    let png_file = open_png(&path)?;
+    let png_file = read_png(&path)?;
+    decode_message(&png_file, &chunk_type)
+}
+
+/// What [`decode_message`] returns for a PNG without a message
+pub const NO_MESSAGE: &str = "No secret message found";
 
-    let chunk = png_file.chunk_by_type(&chunk_type);
-    if let Some(x) = chunk {
-        Ok((x.data_as_string().map_err(|_| Error::StrConversion)?).to_string())
-    } else {
-        Ok("No secret message found".to_string())
+/// Returns the message in the first chunk of `chunk_type` of a PNG in memory, or
+/// [`NO_MESSAGE`] if there is none
+pub fn decode_message(png: &png::Png, chunk_type: &str) -> Result<String, Error> {
+    Ok(find_message(png, chunk_type)?.unwrap_or_else(|| NO_MESSAGE.to_string()))
+}
+
+/// Returns the message in the first chunk of `chunk_type` of a PNG in memory, if any
+///
+/// # Examples
+///
+/// ```
+/// use pngme_lib::{find_message, Png};
+/// let png = Png::from_chunks(vec![]);
+/// assert_eq!(find_message(&png, "ruSt").unwrap(), None);
+/// ```
+pub fn find_message(png: &png::Png, chunk_type: &str) -> Result<Option<String>, Error> {
+    match png.chunk_by_type(chunk_type) {
+        Some(chunk) => Ok(Some(
+            chunk.data_as_string().map_err(|_| Error::StrConversion)?,
+        )),
+        None => Ok(None),
     }
 }
 
@@ -144,15 +277,20 @@ pub fn decode(path: PathBuf, chunk_type: String) -> Result<String, Error> {
 /// remove(path, chunk_type).unwrap();
 /// ```
 pub fn remove(path: PathBuf, chunk_type: String) -> Result<(), Error> {
-    let mut png_file = open_png(&path)?;
-
-    png_file
-        .remove_first_chunk(&chunk_type)
-        .map_err(|_| Error::ChunkNotFound { chunk_type })?;
-    write_png(path, png_file)?;
+    let mut png_file = read_png(&path)?;
+    remove_message(&mut png_file, &chunk_type)?;
+    write_png(&path, &png_file)?;
     Ok(())
 }
 
+/// Removes and returns the first chunk of `chunk_type` from a PNG in memory
+pub fn remove_message(png: &mut png::Png, chunk_type: &str) -> Result<chunk::Chunk, Error> {
+    png.remove_first_chunk(chunk_type)
+        .map_err(|_| Error::ChunkNotFound {
+            chunk_type: chunk_type.to_string(),
+        })
+}
+
 /// Prints all of the chunks in a PNG file
 ///
 /// # Examples
@@ -164,10 +302,150 @@ pub fn remove(path: PathBuf, chunk_type: String) -> Result<(), Error> {
 /// print_chunks(path).unwrap();
 /// ```
 pub fn print_chunks(path: PathBuf) -> Result<(), Error> {
-    let png_file = open_png(&path)?;
+    let png_file = read_png(&path)?;
 
     for chunk in png_file.chunks() {
         println!("{}", chunk);
     }
     Ok(())
 }
+
+/// Removes every chunk not kept by `policy` from a PNG file, saves the result and
+/// returns the removed chunks
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use pngme_lib::{strip, StripPolicy, StripPreset};
+/// let path = PathBuf::from("dice.png");
+/// let removed = strip(path, &StripPolicy::from(StripPreset::Minimal)).unwrap();
+/// for chunk in removed {
+///     println!("Removed {}", chunk.chunk_type());
+/// }
+/// ```
+pub fn strip(path: PathBuf, policy: &StripPolicy) -> Result<Vec<chunk::Chunk>, Error> {
+    let mut png_file = read_png(&path)?;
+
+    let removed = png_file.strip(policy);
+    write_png(&path, &png_file)?;
+    Ok(removed)
+}
+
+/// Copies the ancillary chunks of the PNG file at `source` into the PNG file at `target`
+/// and saves the result. See [`copy_chunks`] for the rules deciding what is copied.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use pngme_lib::{transplant, TransplantOptions};
+/// let source = PathBuf::from("original.png");
+/// let target = PathBuf::from("regenerated.png");
+/// let report = transplant(source, target, &TransplantOptions::default()).unwrap();
+/// println!("Copied {} chunks", report.copied.len());
+/// ```
+pub fn transplant(
+    source: PathBuf,
+    target: PathBuf,
+    options: &TransplantOptions,
+) -> Result<TransplantReport, Error> {
+    let source_file = read_png(&source)?;
+    let mut target_file = read_png(&target)?;
+
+    let report = copy_chunks(&source_file, &mut target_file, options);
+    write_png(&target, &target_file)?;
+    Ok(report)
+}
+
+/// Compares the chunks of two PNG files, optionally including a hex diff of the data of
+/// modified chunks
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use pngme_lib::diff;
+/// let old = PathBuf::from("dice.png");
+/// let new = PathBuf::from("dice-encoded.png");
+/// let changes = diff(old, new, false).unwrap();
+/// print!("{}", changes);
+/// ```
+pub fn diff(old: PathBuf, new: PathBuf, include_hex: bool) -> Result<PngDiff, Error> {
+    let old_file = read_png(&old)?;
+    let new_file = read_png(&new)?;
+
+    Ok(diff_pngs(&old_file, &new_file, include_hex))
+}
+
+/// Checks the structure of a PNG file and returns every problem found. An empty list
+/// means the file is valid. See [`validate_bytes`] for the checks performed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use pngme_lib::validate;
+/// let path = PathBuf::from("dice.png");
+/// for issue in validate(path).unwrap() {
+///     println!("{}", issue);
+/// }
+/// ```
+pub fn validate(path: PathBuf) -> Result<Vec<ValidationIssue>, Error> {
+    let data = fs::read(&path).context(FileNotFoundSnafu { path })?;
+    Ok(validate_bytes(&data))
+}
+
+/// Embeds every row of the manifest at `manifest`, logging the result of each row as a
+/// JSON line to `log`. Running it again skips the rows the log records as completed.
+/// See [`encode_rows`] for details.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use pngme_lib::{bulk_encode, RowStatus};
+/// let manifest = PathBuf::from("recipients.csv");
+/// let log = PathBuf::from("recipients.log.jsonl");
+/// let report = bulk_encode(manifest, log).unwrap();
+/// println!("{} rows failed", report.count(RowStatus::Failed));
+/// ```
+pub fn bulk_encode(manifest: PathBuf, log: PathBuf) -> Result<BulkReport, Error> {
+    let rows = read_manifest(&manifest)?;
+    encode_rows(&rows, &log)
+}
+
+/// Writes the PNG file at `path` in the text form described by [`dump_png`]
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use pngme_lib::{dump, DataEncoding};
+/// let path = PathBuf::from("dice.png");
+/// print!("{}", dump(path, DataEncoding::Hex).unwrap());
+/// ```
+pub fn dump(path: PathBuf, encoding: DataEncoding) -> Result<String, Error> {
+    let png_file = read_png(&path)?;
+
+    Ok(dump_png(&png_file, encoding))
+}
+
+/// Reads the text form written by [`dump`] from `dump_path` and writes the PNG it
+/// describes to `output`, creating or replacing the file
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use pngme_lib::build;
+/// let dump_path = PathBuf::from("dice.png.txt");
+/// let output = PathBuf::from("dice.png");
+/// build(dump_path, output).unwrap();
+/// ```
+pub fn build(dump_path: PathBuf, output: PathBuf) -> Result<(), Error> {
+    let text = fs::read_to_string(&dump_path).context(FileNotFoundSnafu { path: dump_path })?;
+    let png_file = parse_dump(&text)?;
+    let file = fs::File::create(&output).context(PNGWriteSnafu)?;
+    write_png_to(file, &png_file)
+}
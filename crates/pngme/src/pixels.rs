@@ -0,0 +1,466 @@
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+use crate::ihdr::Ihdr;
+use crate::png::Png;
+use crate::Error;
+
+/// The Adam7 passes as first column, first row, column step and row step
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// The image samples stored in the `IDAT` chunks of a PNG
+///
+/// Samples are kept exactly as stored: indexed images hold palette indexes and 16-bit
+/// images keep all 16 bits, so the low bits where data is usually hidden are unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pixels {
+    ihdr: Ihdr,
+    channels: usize,
+    /// Row-major samples, with the channels of each pixel next to each other
+    samples: Vec<u16>,
+}
+
+/// One reduced image of an interlaced PNG, or the whole image otherwise
+struct Pass {
+    x: usize,
+    y: usize,
+    dx: usize,
+    dy: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Pixels {
+    /// The most samples an image may hold, 2^28, so a forged header cannot make
+    /// [`Pixels::decode`] allocate gigabytes
+    pub const MAX_SAMPLES: usize = 1 << 28;
+
+    /// Decompresses, unfilters and deinterlaces the `IDAT` data of `png`. Images with
+    /// more than [`Pixels::MAX_SAMPLES`] samples are refused.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use pngme_lib::{read_png, Pixels};
+    /// let pixels = Pixels::decode(&read_png(Path::new("dice.png")).unwrap()).unwrap();
+    /// println!("{} bytes fit in the lowest bit", pixels.lsb_capacity(1));
+    /// ```
+    pub fn decode(png: &Png) -> Result<Pixels, Error> {
+        let error = |reason: String| Error::PixelDecode { reason };
+        let ihdr = png
+            .chunk_by_type("IHDR")
+            .and_then(Ihdr::from_chunk)
+            .ok_or_else(|| error("missing or invalid IHDR chunk".to_string()))?;
+        let channels = match (ihdr.color_type, ihdr.bit_depth) {
+            (0, 1 | 2 | 4 | 8 | 16) | (3, 1 | 2 | 4 | 8) => 1,
+            (4, 8 | 16) => 2,
+            (2, 8 | 16) => 3,
+            (6, 8 | 16) => 4,
+            (color_type, bit_depth) => {
+                return Err(error(format!(
+                    "bit depth {} is not allowed for color type {}",
+                    bit_depth, color_type
+                )))
+            }
+        };
+        if ihdr.compression_method != 0 || ihdr.filter_method != 0 {
+            return Err(error("unknown compression or filter method".to_string()));
+        }
+        if ihdr.interlace_method > 1 {
+            return Err(error(format!(
+                "unknown interlace method {}",
+                ihdr.interlace_method
+            )));
+        }
+        if ihdr.width == 0 || ihdr.height == 0 {
+            return Err(error("the image has no pixels".to_string()));
+        }
+        let too_large = || {
+            error(format!(
+                "{}x{} pixels are more than the {} samples an image may hold",
+                ihdr.width,
+                ihdr.height,
+                Pixels::MAX_SAMPLES
+            ))
+        };
+        let sample_count = (ihdr.width as usize)
+            .checked_mul(ihdr.height as usize)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .filter(|&samples| samples <= Pixels::MAX_SAMPLES)
+            .ok_or_else(too_large)?;
+        let bits_per_pixel = channels * ihdr.bit_depth as usize;
+        let row_bytes = |width: usize| Some(width.checked_mul(bits_per_pixel)?.div_ceil(8));
+        let passes = passes(&ihdr);
+        let expected = passes
+            .iter()
+            .try_fold(0usize, |total, pass| {
+                let bytes = pass.height.checked_mul(row_bytes(pass.width)? + 1)?;
+                total.checked_add(bytes)
+            })
+            .ok_or_else(too_large)?;
+
+        let compressed: Vec<u8> = png
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().bytes() == *b"IDAT")
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect();
+        if compressed.is_empty() {
+            return Err(error("no IDAT chunks".to_string()));
+        }
+        let data = decompress_to_vec_zlib_with_limit(&compressed, expected)
+            .map_err(|e| error(format!("cannot decompress IDAT data: {:?}", e.status)))?;
+        if data.len() < expected {
+            return Err(error(format!(
+                "IDAT data holds {} of {} bytes",
+                data.len(),
+                expected
+            )));
+        }
+
+        let mut pixels = Pixels {
+            ihdr,
+            channels,
+            samples: vec![0; sample_count],
+        };
+        let step = (bits_per_pixel / 8).max(1);
+        let mut rest = data.as_slice();
+        for pass in &passes {
+            // Cannot overflow, the rows were summed up in `expected`
+            let length = row_bytes(pass.width).unwrap_or_default();
+            let mut previous = vec![0; length];
+            for row in 0..pass.height {
+                let (line, tail) = rest.split_at(1 + length);
+                rest = tail;
+                let mut current = line[1..].to_vec();
+                unfilter(line[0], &mut current, &previous, step).map_err(|filter| {
+                    error(format!("unknown filter type {} in row {}", filter, row))
+                })?;
+                pixels.store(pass, row, &current);
+                previous = current;
+            }
+        }
+        Ok(pixels)
+    }
+
+    /// Copies the samples of one unfiltered row of `pass` to their place in the image
+    fn store(&mut self, pass: &Pass, row: usize, line: &[u8]) {
+        let width = self.ihdr.width as usize;
+        let y = pass.y + row * pass.dy;
+        for column in 0..pass.width {
+            let x = pass.x + column * pass.dx;
+            for channel in 0..self.channels {
+                let index = column * self.channels + channel;
+                self.samples[(y * width + x) * self.channels + channel] =
+                    read_sample(line, index, self.ihdr.bit_depth);
+            }
+        }
+    }
+
+    /// The header the pixels were decoded with
+    pub fn ihdr(&self) -> &Ihdr {
+        &self.ihdr
+    }
+
+    /// Image width in pixels
+    pub fn width(&self) -> u32 {
+        self.ihdr.width
+    }
+
+    /// Image height in pixels
+    pub fn height(&self) -> u32 {
+        self.ihdr.height
+    }
+
+    /// Names of the channels of each pixel, in the order they are stored
+    pub fn channel_names(&self) -> &'static [&'static str] {
+        match self.ihdr.color_type {
+            0 => &["gray"],
+            2 => &["red", "green", "blue"],
+            3 => &["index"],
+            4 => &["gray", "alpha"],
+            _ => &["red", "green", "blue", "alpha"],
+        }
+    }
+
+    /// The sample of `channel` for the pixel at column `x` and row `y`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel or channel is outside the image.
+    pub fn sample(&self, x: u32, y: u32, channel: usize) -> u16 {
+        assert!(x < self.ihdr.width && y < self.ihdr.height && channel < self.channels);
+        let pixel = y as usize * self.ihdr.width as usize + x as usize;
+        self.samples[pixel * self.channels + channel]
+    }
+
+    /// Whether `bit` is set in the samples of `channel`, row by row. Bit 0 is the least
+    /// significant bit. Returns `None` if the image has no such channel or its samples
+    /// have fewer bits.
+    pub fn bit_plane(&self, channel: usize, bit: u8) -> Option<Vec<bool>> {
+        if channel >= self.channels || bit >= self.ihdr.bit_depth {
+            return None;
+        }
+        let plane = self
+            .samples
+            .iter()
+            .skip(channel)
+            .step_by(self.channels)
+            .map(|sample| sample >> bit & 1 == 1)
+            .collect();
+        Some(plane)
+    }
+
+    /// Bytes that fit in the lowest `bits` bits of every sample, leaving out the alpha
+    /// channel since changes to transparent pixels are easy to spot
+    pub fn lsb_capacity(&self, bits: u8) -> usize {
+        let channels = match self.ihdr.color_type {
+            4 | 6 => self.channels - 1,
+            _ => self.channels,
+        };
+        let bits = bits.min(self.ihdr.bit_depth) as usize;
+        self.ihdr.width as usize * self.ihdr.height as usize * channels * bits / 8
+    }
+
+    /// The summed absolute difference of all channels for every pixel, row by row.
+    /// Returns `None` if the images differ in size, color type or bit depth.
+    pub fn difference(&self, other: &Pixels) -> Option<Vec<u32>> {
+        let same = |a: &Ihdr, b: &Ihdr| {
+            (a.width, a.height, a.color_type, a.bit_depth)
+                == (b.width, b.height, b.color_type, b.bit_depth)
+        };
+        if !same(&self.ihdr, &other.ihdr) {
+            return None;
+        }
+        let difference = self
+            .samples
+            .chunks(self.channels)
+            .zip(other.samples.chunks(self.channels))
+            .map(|(a, b)| a.iter().zip(b).map(|(a, b)| a.abs_diff(*b) as u32).sum())
+            .collect();
+        Some(difference)
+    }
+}
+
+/// The passes the image data is stored in, leaving out passes without pixels
+fn passes(ihdr: &Ihdr) -> Vec<Pass> {
+    let (width, height) = (ihdr.width as usize, ihdr.height as usize);
+    if ihdr.interlace_method == 0 {
+        return vec![Pass {
+            x: 0,
+            y: 0,
+            dx: 1,
+            dy: 1,
+            width,
+            height,
+        }];
+    }
+    ADAM7
+        .iter()
+        .map(|&(x, y, dx, dy)| Pass {
+            x,
+            y,
+            dx,
+            dy,
+            width: (width + dx - 1 - x) / dx,
+            height: (height + dy - 1 - y) / dy,
+        })
+        .filter(|pass| pass.width > 0 && pass.height > 0)
+        .collect()
+}
+
+/// Reverses the filter of one row, using the unfiltered row above in `previous`.
+/// `step` is the number of bytes per pixel, at least 1. Returns unknown filter types.
+fn unfilter(filter: u8, line: &mut [u8], previous: &[u8], step: usize) -> Result<(), u8> {
+    for i in 0..line.len() {
+        let left = if i >= step { line[i - step] } else { 0 };
+        let above = previous[i];
+        let upper_left = if i >= step { previous[i - step] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => above,
+            3 => ((left as u16 + above as u16) / 2) as u8,
+            4 => paeth(left, above, upper_left),
+            _ => return Err(filter),
+        };
+        line[i] = line[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let estimate = left as i16 + above as i16 - upper_left as i16;
+    let (a, b, c) = (
+        (estimate - left as i16).abs(),
+        (estimate - above as i16).abs(),
+        (estimate - upper_left as i16).abs(),
+    );
+    if a <= b && a <= c {
+        left
+    } else if b <= c {
+        above
+    } else {
+        upper_left
+    }
+}
+
+/// Reads sample number `index` from an unfiltered row
+fn read_sample(line: &[u8], index: usize, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => u16::from_be_bytes([line[2 * index], line[2 * index + 1]]),
+        8 => line[index] as u16,
+        _ => {
+            let bit = index * bit_depth as usize;
+            let shift = 8 - bit_depth as usize - bit % 8;
+            (line[bit / 8] >> shift & ((1 << bit_depth) - 1)) as u16
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use miniz_oxide::deflate::compress_to_vec_zlib;
+    use std::str::FromStr;
+
+    /// Builds a PNG from unfiltered or hand-filtered scanlines
+    fn png(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        color_type: u8,
+        interlace: u8,
+        raw: &[u8],
+    ) -> Png {
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[bit_depth, color_type, 0, 0, interlace]);
+        let chunk = |chunk_type: &str, data: Vec<u8>| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+        };
+        Png::from_chunks(vec![
+            chunk("IHDR", ihdr),
+            chunk("IDAT", compress_to_vec_zlib(raw, 6)),
+            chunk("IEND", vec![]),
+        ])
+    }
+
+    fn row(pixels: &Pixels, y: u32) -> Vec<u16> {
+        (0..pixels.width())
+            .map(|x| pixels.sample(x, y, 0))
+            .collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        #[rustfmt::skip]
+        let raw = [
+            1, 10, 5, 5, // Sub
+            2, 1, 1, 1, // Up
+            3, 0, 0, 0, // Average
+            4, 0, 0, 0, // Paeth
+        ];
+        let pixels = Pixels::decode(&png(3, 4, 8, 0, 0, &raw)).unwrap();
+        assert_eq!(row(&pixels, 0), [10, 15, 20]);
+        assert_eq!(row(&pixels, 1), [11, 16, 21]);
+        assert_eq!(row(&pixels, 2), [5, 10, 15]);
+        assert_eq!(row(&pixels, 3), [5, 10, 15]);
+    }
+
+    #[test]
+    fn test_sub_byte_samples() {
+        let pixels = Pixels::decode(&png(10, 1, 1, 0, 0, &[0, 0b1010_1010, 0b1100_0000])).unwrap();
+        assert_eq!(row(&pixels, 0), [1, 0, 1, 0, 1, 0, 1, 0, 1, 1]);
+        assert_eq!(
+            pixels.bit_plane(0, 0).unwrap(),
+            [true, false, true, false, true, false, true, false, true, true]
+        );
+        assert_eq!(pixels.bit_plane(0, 1), None);
+        assert_eq!(pixels.bit_plane(1, 0), None);
+        assert_eq!(pixels.lsb_capacity(8), 1);
+    }
+
+    #[test]
+    fn test_sixteen_bit_channels() {
+        let raw = [0, 0x12, 0x34, 0, 1, 0xff, 0xff, 0x80, 0x00];
+        let pixels = Pixels::decode(&png(1, 1, 16, 6, 0, &raw)).unwrap();
+        assert_eq!(pixels.channel_names(), ["red", "green", "blue", "alpha"]);
+        assert_eq!(pixels.sample(0, 0, 0), 0x1234);
+        assert_eq!(pixels.sample(0, 0, 3), 0x8000);
+        assert_eq!(pixels.bit_plane(1, 0), Some(vec![true]));
+        assert_eq!(pixels.bit_plane(1, 15), Some(vec![false]));
+        assert_eq!(pixels.bit_plane(1, 16), None);
+        assert_eq!(pixels.bit_plane(4, 0), None);
+        // Three 16-bit color samples without alpha
+        assert_eq!(pixels.lsb_capacity(16), 6);
+    }
+
+    #[test]
+    fn test_adam7() {
+        #[rustfmt::skip]
+        let raw = [
+            0, 0, // pass 1
+            0, 2, // pass 4
+            0, 6, 8, // pass 5
+            0, 1, 0, 7, // pass 6
+            0, 3, 4, 5, // pass 7
+        ];
+        let pixels = Pixels::decode(&png(3, 3, 8, 0, 1, &raw)).unwrap();
+        let samples: Vec<u16> = (0..3).flat_map(|y| row(&pixels, y)).collect();
+        assert_eq!(samples, (0..9).collect::<Vec<u16>>());
+    }
+
+    #[test]
+    fn test_difference() {
+        let cover = Pixels::decode(&png(2, 1, 8, 2, 0, &[0, 10, 10, 10, 20, 20, 20])).unwrap();
+        let stego = Pixels::decode(&png(2, 1, 8, 2, 0, &[0, 11, 10, 11, 20, 20, 20])).unwrap();
+        assert_eq!(cover.difference(&stego), Some(vec![2, 0]));
+        let gray = Pixels::decode(&png(2, 1, 8, 0, 0, &[0, 10, 20])).unwrap();
+        assert_eq!(cover.difference(&gray), None);
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        assert!(matches!(
+            Pixels::decode(&png(3, 1, 8, 0, 0, &[0, 1, 2])),
+            Err(Error::PixelDecode { .. })
+        ));
+        assert!(matches!(
+            Pixels::decode(&png(1, 1, 8, 0, 0, &[5, 1])),
+            Err(Error::PixelDecode { .. })
+        ));
+        assert!(matches!(
+            Pixels::decode(&png(1, 1, 16, 3, 0, &[0, 1, 1])),
+            Err(Error::PixelDecode { .. })
+        ));
+        assert!(Pixels::decode(&Png::from_chunks(vec![])).is_err());
+        // Unknown interlace method
+        assert!(matches!(
+            Pixels::decode(&png(1, 1, 8, 0, 2, &[0, 1])),
+            Err(Error::PixelDecode { .. })
+        ));
+    }
+
+    #[test]
+    fn test_huge_dimensions() {
+        let Err(Error::PixelDecode { reason }) =
+            Pixels::decode(&png(u32::MAX, u32::MAX, 16, 6, 0, &[0]))
+        else {
+            panic!("decoding a 4294967295x4294967295 image succeeded");
+        };
+        assert!(reason.contains("more than"), "{}", reason);
+        assert!(Pixels::decode(&png(1 << 15, 1 << 14, 8, 0, 1, &[0])).is_err());
+    }
+}
@@ -1,26 +1,36 @@
 use std::fmt;
 
-use crate::{chunk::Chunk, chunk_type::ChunkType};
+use crate::{chunk::Chunk, chunk_type::ChunkType, strip::StripPolicy};
 
+use serde::Serialize;
 use snafu::prelude::*;
 
+/// Errors that can be returned when working with a `Png`
 #[derive(Debug, Snafu)]
 pub enum PngError {
+    /// No chunk of the requested type exists
     #[snafu(display("Chunk type not found for: {chunk_type}"))]
-    ChunkNotFound { chunk_type: String },
+    ChunkNotFound {
+        /// The chunk type that was searched for
+        chunk_type: String,
+    },
+    /// No message chunk of the requested type exists
     #[snafu(display("No message found for chunk type: {}", chunk_type))]
-    NoMessageFound { chunk_type: String },
+    NoMessageFound {
+        /// The chunk type that was searched for
+        chunk_type: String,
+    },
 }
 
 /// A PNG container as described by the PNG spec
 /// <http://www.libpng.org/pub/png/spec/1.2/PNG-Contents.html>
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Png {
     chunks: Vec<Chunk>,
 }
 
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub(crate) const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
     /// Creates a `Png` from a list of chunks using the correct header
     pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
@@ -32,6 +42,25 @@ impl Png {
         self.chunks.push(chunk);
     }
 
+    /// Inserts a chunk at position `index` of this `Png` file's `Chunk` list, shifting
+    /// all chunks after it. Panics if `index` is greater than the number of chunks.
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) {
+        self.chunks.insert(index, chunk);
+    }
+
+    /// Removes and returns the chunk at position `index`, shifting all chunks after it.
+    /// Panics if `index` is out of bounds.
+    pub fn remove_chunk(&mut self, index: usize) -> Chunk {
+        self.chunks.remove(index)
+    }
+
+    /// Moves the chunk at position `from` so that it ends up at position `to`, shifting
+    /// the chunks in between. Panics if either index is out of bounds.
+    pub fn move_chunk(&mut self, from: usize, to: usize) {
+        let chunk = self.chunks.remove(from);
+        self.chunks.insert(to, chunk);
+    }
+
     /// Searches for a `Chunk` with the specified `chunk_type` and removes the first
     /// matching `Chunk` from this `Png` list of chunks.
     pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk, PngError> {
@@ -56,10 +85,40 @@ impl Png {
             })
         }
     }
+    /// Removes every `Chunk` that `policy` does not keep and returns the removed chunks
+    /// in their original order.
+    pub fn strip(&mut self, policy: &StripPolicy) -> Vec<Chunk> {
+        let (kept, removed) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|c| policy.keeps(c.chunk_type()));
+        self.chunks = kept;
+        removed
+    }
     /// Lists the `Chunk`s stored in this `Png`
     pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
+    /// The byte offset of each `Chunk` from the start of the file, including the header
+    pub fn chunk_offsets(&self) -> Vec<usize> {
+        self.chunks
+            .iter()
+            .scan(Png::STANDARD_HEADER.len(), |offset, chunk| {
+                let current = *offset;
+                *offset += chunk.as_bytes().len();
+                Some(current)
+            })
+            .collect()
+    }
+    /// Returns the `Chunk` at position `index`, or `None` if there are not that many chunks.
+    pub fn chunk_at(&self, index: usize) -> Option<&Chunk> {
+        self.chunks.get(index)
+    }
+    /// The position of the first `Chunk` with the specified `chunk_type`
+    pub fn position_of(&self, chunk_type: &str) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
+    }
     /// Searches for a `Chunk` with the specified `chunk_type` and returns the first
     /// matching `Chunk` from this `Png`.
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
@@ -81,7 +140,11 @@ impl Png {
 }
 
 use nom::{
-    bytes::complete::take, error::ErrorKind, multi::many0, number::complete::be_i32, IResult,
+    bytes::complete::take,
+    error::ErrorKind,
+    multi::many0,
+    number::complete::{be_i32, be_u32},
+    IResult,
 };
 fn parse_chunk(input: &[u8]) -> IResult<&[u8], Chunk> {
     //length
@@ -91,15 +154,20 @@ fn parse_chunk(input: &[u8]) -> IResult<&[u8], Chunk> {
     // data of length
     let (input, data) = take(length as usize)(input)?;
     // crc: 4 bytes
-    let (input, _) = take(4usize)(input)?;
+    let (input, crc) = be_u32(input)?;
     let chunk_type_array = [
         chunk_type_raw[0],
         chunk_type_raw[1],
         chunk_type_raw[2],
         chunk_type_raw[3],
     ];
-    let chunk_type = ChunkType::try_from(chunk_type_array).unwrap();
-    let chunk = Chunk::new(chunk_type, data.into());
+    let chunk_type = ChunkType::try_from(chunk_type_array).map_err(|_| {
+        nom::Err::Error(nom::error::Error {
+            code: ErrorKind::Verify,
+            input,
+        })
+    })?;
+    let chunk = Chunk::new(chunk_type, data.into()).with_stored_crc(crc);
     Ok((input, chunk))
 }
 
@@ -128,6 +196,43 @@ impl TryFrom<&[u8]> for Png {
     }
 }
 
+#[derive(Serialize)]
+struct ChunkEntry<'a> {
+    index: usize,
+    offset: usize,
+    #[serde(flatten)]
+    chunk: &'a Chunk,
+}
+
+/// Serializes the total length in bytes and every chunk along with its index and byte
+/// offset in the file
+impl Serialize for Png {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct PngEntry<'a> {
+            length: usize,
+            chunks: Vec<ChunkEntry<'a>>,
+        }
+
+        let chunks = self
+            .chunks
+            .iter()
+            .zip(self.chunk_offsets())
+            .enumerate()
+            .map(|(index, (chunk, offset))| ChunkEntry {
+                index,
+                offset,
+                chunk,
+            })
+            .collect();
+        PngEntry {
+            length: self.as_bytes().len(),
+            chunks,
+        }
+        .serialize(serializer)
+    }
+}
+
 impl fmt::Display for Png {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Png {{",)?;
@@ -256,6 +361,39 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = testing_png();
+        png.insert_chunk(1, chunk_from_strings("TeSt", "Message").unwrap());
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+        assert_eq!(&png.chunks()[2].chunk_type().to_string(), "miDl");
+    }
+
+    #[test]
+    fn test_remove_and_move_chunk() {
+        let mut png = testing_png();
+        png.move_chunk(0, 2);
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["miDl", "LASt", "FrSt"]);
+        let removed = png.remove_chunk(1);
+        assert_eq!(&removed.chunk_type().to_string(), "LASt");
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_chunk_at_and_position_of() {
+        let png = testing_png();
+        assert_eq!(&png.chunk_at(1).unwrap().chunk_type().to_string(), "miDl");
+        assert!(png.chunk_at(3).is_none());
+        assert_eq!(png.position_of("LASt"), Some(2));
+        assert_eq!(png.position_of("IEND"), None);
+    }
+
     #[test]
     fn test_remove_first_chunk() {
         let mut png = testing_png();
@@ -265,6 +403,39 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_strip_deny() {
+        use std::str::FromStr;
+
+        let mut png = testing_png();
+        let policy = StripPolicy::Deny(vec![ChunkType::from_str("miDl").unwrap()]);
+        let removed = png.strip(&policy);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_strip_allow_keeps_critical() {
+        let mut png = testing_png();
+        let removed = png.strip(&StripPolicy::Allow(vec![]));
+        assert_eq!(removed.len(), 1);
+        assert!(png.chunk_by_type("FrSt").is_some());
+        assert!(png.chunk_by_type("LASt").is_some());
+    }
+
+    #[test]
+    fn test_strip_metadata_removes_hidden_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("ruSt", "hidden").unwrap());
+        png.append_chunk(chunk_from_strings("gAMA", "gamma").unwrap());
+        let removed = png.strip(&StripPolicy::from(crate::strip::StripPreset::Metadata));
+        let removed: Vec<String> = removed.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert!(removed.contains(&"ruSt".to_string()), "{:?}", removed);
+        assert!(png.chunk_by_type("ruSt").is_none());
+        assert!(png.chunk_by_type("gAMA").is_some());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -281,6 +452,23 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_chunk_offsets() {
+        let png = testing_png();
+        assert_eq!(png.chunk_offsets(), vec![8, 40, 70]);
+    }
+
+    #[test]
+    fn test_png_serialize() {
+        let png = Png::from_chunks(vec![chunk_from_strings("TeSt", "Message").unwrap()]);
+        let json = serde_json::to_value(&png).unwrap();
+        assert_eq!(json["length"], 27);
+        assert_eq!(json["chunks"][0]["index"], 0);
+        assert_eq!(json["chunks"][0]["offset"], 8);
+        assert_eq!(json["chunks"][0]["chunk_type"], "TeSt");
+        assert_eq!(json["chunks"][0]["length"], 7);
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
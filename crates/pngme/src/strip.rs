@@ -0,0 +1,113 @@
+use std::str::FromStr;
+
+use crate::chunk_type::ChunkType;
+
+/// Decides which chunks survive when stripping a `Png`.
+///
+/// Critical chunks (`IHDR`, `PLTE`, `IDAT`, `IEND`) are always kept, as removing
+/// them would leave an image that can no longer be displayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StripPolicy {
+    /// Keep only the listed ancillary chunk types and remove everything else
+    Allow(Vec<ChunkType>),
+    /// Remove only the listed chunk types and keep everything else
+    Deny(Vec<ChunkType>),
+    /// Remove the listed chunk types and every private ancillary chunk, such as the ones
+    /// messages are hidden in, and keep everything else
+    DenyWithPrivate(Vec<ChunkType>),
+}
+
+impl StripPolicy {
+    /// Returns true if a chunk of `chunk_type` should be kept under this policy
+    pub fn keeps(&self, chunk_type: &ChunkType) -> bool {
+        if chunk_type.is_critical() {
+            return true;
+        }
+        match self {
+            StripPolicy::Allow(types) => types.contains(chunk_type),
+            StripPolicy::Deny(types) => !types.contains(chunk_type),
+            StripPolicy::DenyWithPrivate(types) => {
+                chunk_type.is_public() && !types.contains(chunk_type)
+            }
+        }
+    }
+}
+
+impl From<StripPreset> for StripPolicy {
+    fn from(preset: StripPreset) -> Self {
+        match preset {
+            StripPreset::Minimal => StripPolicy::Allow(vec![]),
+            StripPreset::Web => {
+                StripPolicy::Allow(chunk_types(&["sRGB", "gAMA", "cHRM", "iCCP", "tRNS"]))
+            }
+            StripPreset::Metadata => {
+                StripPolicy::DenyWithPrivate(chunk_types(&["eXIf", "tEXt", "zTXt", "iTXt", "tIME"]))
+            }
+        }
+    }
+}
+
+/// Named strip policies for common clean-up tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StripPreset {
+    /// Keep only critical chunks
+    Minimal,
+    /// Keep critical chunks plus the colour and transparency chunks that change how
+    /// the image is rendered
+    Web,
+    /// Remove EXIF, text and timestamp metadata and private chunks such as hidden
+    /// messages, and keep everything else
+    Metadata,
+}
+
+fn chunk_types(names: &[&str]) -> Vec<ChunkType> {
+    names
+        .iter()
+        .map(|name| ChunkType::from_str(name).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_type(name: &str) -> ChunkType {
+        ChunkType::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_critical_always_kept() {
+        let policy = StripPolicy::Deny(vec![chunk_type("IDAT")]);
+        assert!(policy.keeps(&chunk_type("IDAT")));
+    }
+
+    #[test]
+    fn test_allow_policy() {
+        let policy = StripPolicy::Allow(vec![chunk_type("gAMA")]);
+        assert!(policy.keeps(&chunk_type("gAMA")));
+        assert!(!policy.keeps(&chunk_type("tEXt")));
+    }
+
+    #[test]
+    fn test_deny_policy() {
+        let policy = StripPolicy::Deny(vec![chunk_type("tEXt")]);
+        assert!(!policy.keeps(&chunk_type("tEXt")));
+        assert!(policy.keeps(&chunk_type("ruSt")));
+    }
+
+    #[test]
+    fn test_presets() {
+        let minimal = StripPolicy::from(StripPreset::Minimal);
+        assert!(!minimal.keeps(&chunk_type("sRGB")));
+
+        let web = StripPolicy::from(StripPreset::Web);
+        assert!(web.keeps(&chunk_type("sRGB")));
+        assert!(!web.keeps(&chunk_type("ruSt")));
+
+        let metadata = StripPolicy::from(StripPreset::Metadata);
+        assert!(!metadata.keeps(&chunk_type("eXIf")));
+        assert!(!metadata.keeps(&chunk_type("ruSt")));
+        assert!(metadata.keeps(&chunk_type("gAMA")));
+        assert!(metadata.keeps(&chunk_type("IHDR")));
+    }
+}
@@ -0,0 +1,237 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+/// Ancillary chunk types the PNG spec allows at most once per file. Any other type, such
+/// as the text chunks, may appear many times.
+const SINGLE_INSTANCE: &[&[u8; 4]] = &[
+    b"acTL", b"bKGD", b"cHRM", b"cICP", b"cLLI", b"eXIf", b"gAMA", b"hIST", b"iCCP", b"mDCV",
+    b"oFFs", b"pCAL", b"pHYs", b"sBIT", b"sCAL", b"sRGB", b"sTER", b"tIME", b"tRNS",
+];
+
+/// Overrides applied on top of the safe-to-copy rules when copying chunks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransplantOptions {
+    /// Chunk types that are copied even if they are unsafe to copy
+    pub include: Vec<ChunkType>,
+    /// Chunk types that are never copied
+    pub exclude: Vec<ChunkType>,
+}
+
+/// Why an ancillary chunk was not copied to the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The chunk type was listed in `TransplantOptions::exclude`
+    Excluded,
+    /// The target already contains a chunk of this single-instance type, or the same
+    /// chunk with the same data
+    AlreadyPresent,
+    /// The critical chunks differ and this chunk is not safe to copy
+    UnsafeToCopy,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Excluded => write!(f, "excluded"),
+            SkipReason::AlreadyPresent => write!(f, "already present in target"),
+            SkipReason::UnsafeToCopy => write!(f, "unsafe to copy after image data changed"),
+        }
+    }
+}
+
+/// The outcome of copying chunks from one `Png` to another
+#[derive(Debug, Clone, Default)]
+pub struct TransplantReport {
+    /// Chunks that were copied, in source order
+    pub copied: Vec<Chunk>,
+    /// Chunks that were left behind, in source order
+    pub skipped: Vec<(Chunk, SkipReason)>,
+}
+
+/// Copies the ancillary chunks of `source` into `target`.
+///
+/// Following the PNG spec, chunks whose safe-to-copy bit is unset are only copied when
+/// the critical chunks of both files are identical, unless listed in
+/// `TransplantOptions::include`. Single-instance chunk types such as `gAMA` are skipped if
+/// the target already contains one, so they are never duplicated. Chunks that may appear
+/// many times, such as `tEXt`, are always copied unless the target holds an identical
+/// chunk, which keeps repeated transplants from piling up copies. Chunks found before the
+/// first `IDAT` in `source` are inserted before the first `IDAT` in `target`, the rest
+/// are inserted before `IEND`.
+pub fn copy_chunks(
+    source: &Png,
+    target: &mut Png,
+    options: &TransplantOptions,
+) -> TransplantReport {
+    let image_changed = critical_chunks(source).ne(critical_chunks(target));
+
+    let mut report = TransplantReport::default();
+    let mut seen_idat = false;
+    for chunk in source.chunks() {
+        let chunk_type = chunk.chunk_type();
+        if is_idat(chunk_type) {
+            seen_idat = true;
+        }
+        if chunk_type.is_critical() {
+            continue;
+        }
+        let skip = if options.exclude.contains(chunk_type) {
+            Some(SkipReason::Excluded)
+        } else if is_present(target, chunk) {
+            Some(SkipReason::AlreadyPresent)
+        } else if image_changed
+            && !chunk_type.is_safe_to_copy()
+            && !options.include.contains(chunk_type)
+        {
+            Some(SkipReason::UnsafeToCopy)
+        } else {
+            None
+        };
+        match skip {
+            Some(reason) => report.skipped.push((chunk.clone(), reason)),
+            None => {
+                let index = if seen_idat {
+                    target.position_of("IEND")
+                } else {
+                    target.position_of("IDAT")
+                };
+                target.insert_chunk(index.unwrap_or(target.chunks().len()), chunk.clone());
+                report.copied.push(chunk.clone());
+            }
+        }
+    }
+    report
+}
+
+/// Whether `target` already has a chunk that makes copying `chunk` a duplicate
+fn is_present(target: &Png, chunk: &Chunk) -> bool {
+    let single = SINGLE_INSTANCE.contains(&&chunk.chunk_type().bytes());
+    target
+        .chunks()
+        .iter()
+        .filter(|c| c.chunk_type() == chunk.chunk_type())
+        .any(|c| single || c.data() == chunk.data())
+}
+
+fn critical_chunks(png: &Png) -> impl Iterator<Item = (&ChunkType, &[u8])> {
+    png.chunks()
+        .iter()
+        .filter(|c| c.chunk_type().is_critical())
+        .map(|c| (c.chunk_type(), c.data()))
+}
+
+fn is_idat(chunk_type: &ChunkType) -> bool {
+    &chunk_type.bytes() == b"IDAT"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.into())
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
+    fn source() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("gAMA", "gamma"),
+            chunk("IDAT", "pixels"),
+            chunk("ruSt", "secret"),
+            chunk("tEXT", "unsafe"),
+            chunk("IEND", ""),
+        ])
+    }
+
+    #[test]
+    fn test_copy_unchanged_image() {
+        let mut target = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("IDAT", "pixels"),
+            chunk("IEND", ""),
+        ]);
+        let report = copy_chunks(&source(), &mut target, &TransplantOptions::default());
+        assert_eq!(report.copied.len(), 3);
+        assert!(report.skipped.is_empty());
+        assert_eq!(
+            types(&target),
+            vec!["IHDR", "gAMA", "IDAT", "ruSt", "tEXT", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_copy_changed_image_skips_unsafe() {
+        let mut target = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("IDAT", "new pixels"),
+            chunk("IEND", ""),
+        ]);
+        let report = copy_chunks(&source(), &mut target, &TransplantOptions::default());
+        assert_eq!(report.copied.len(), 1);
+        assert_eq!(report.skipped.len(), 2);
+        assert!(report
+            .skipped
+            .iter()
+            .all(|(_, reason)| *reason == SkipReason::UnsafeToCopy));
+        assert_eq!(types(&target), vec!["IHDR", "IDAT", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_copy_overrides() {
+        let mut target = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("gAMA", "other gamma"),
+            chunk("IDAT", "new pixels"),
+            chunk("IEND", ""),
+        ]);
+        let options = TransplantOptions {
+            include: vec![ChunkType::from_str("tEXT").unwrap()],
+            exclude: vec![ChunkType::from_str("ruSt").unwrap()],
+        };
+        let report = copy_chunks(&source(), &mut target, &options);
+        let reasons: Vec<SkipReason> = report.skipped.iter().map(|(_, r)| *r).collect();
+        assert_eq!(
+            reasons,
+            vec![SkipReason::AlreadyPresent, SkipReason::Excluded]
+        );
+        assert_eq!(types(&target), vec!["IHDR", "gAMA", "IDAT", "tEXT", "IEND"]);
+    }
+
+    #[test]
+    fn test_copy_multi_instance_chunks() {
+        let source = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "Author\0alice"),
+            chunk("tEXt", "Title\0dice"),
+            chunk("iTXt", "Comment"),
+            chunk("IDAT", "pixels"),
+            chunk("IEND", ""),
+        ]);
+        let mut target = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "Title\0dice"),
+            chunk("IDAT", "pixels"),
+            chunk("IEND", ""),
+        ]);
+        let report = copy_chunks(&source, &mut target, &TransplantOptions::default());
+        assert_eq!(report.copied.len(), 2);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].0.data(), b"Title\0dice");
+        assert_eq!(
+            types(&target),
+            vec!["IHDR", "tEXt", "tEXt", "iTXt", "IDAT", "IEND"]
+        );
+    }
+}
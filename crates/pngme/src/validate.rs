@@ -0,0 +1,226 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::chunk::CASTAGNOLI;
+use crate::png::Png;
+
+/// A structural problem found while validating a PNG byte stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+pub enum Problem {
+    /// The file does not start with the PNG signature
+    InvalidSignature,
+    /// The file ends part way through a chunk
+    Truncated,
+    /// The chunk type bytes are not ASCII letters
+    InvalidChunkType {
+        /// The raw chunk type bytes
+        bytes: [u8; 4],
+    },
+    /// The stored CRC does not match the chunk type and data
+    CrcMismatch {
+        /// The CRC stored in the file
+        stored: u32,
+        /// The CRC calculated from the chunk type and data
+        computed: u32,
+    },
+    /// The first chunk is not `IHDR`
+    MissingIhdr,
+    /// The file contains no `IDAT` chunk
+    MissingIdat,
+    /// The last chunk is not `IEND`
+    MissingIend,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::InvalidSignature => write!(f, "missing PNG signature"),
+            Problem::Truncated => write!(f, "file ends part way through a chunk"),
+            Problem::InvalidChunkType { bytes } => {
+                write!(f, "invalid chunk type {:?}", String::from_utf8_lossy(bytes))
+            }
+            Problem::CrcMismatch { stored, computed } => write!(
+                f,
+                "CRC mismatch (stored {stored:#010x}, computed {computed:#010x})"
+            ),
+            Problem::MissingIhdr => write!(f, "first chunk is not IHDR"),
+            Problem::MissingIdat => write!(f, "no IDAT chunk"),
+            Problem::MissingIend => write!(f, "last chunk is not IEND"),
+        }
+    }
+}
+
+/// A problem along with where in the file it was found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationIssue {
+    /// Byte offset from the start of the file
+    pub offset: usize,
+    /// Index of the chunk the problem belongs to, if any
+    pub chunk_index: Option<usize>,
+    /// What is wrong
+    #[serde(flatten)]
+    pub problem: Problem,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.chunk_index {
+            Some(index) => write!(
+                f,
+                "chunk {} at byte {}: {}",
+                index, self.offset, self.problem
+            ),
+            None => write!(f, "byte {}: {}", self.offset, self.problem),
+        }
+    }
+}
+
+/// Checks a PNG byte stream for a valid signature, well formed chunks with matching CRCs
+/// and the `IHDR`, `IDAT` and `IEND` chunks every PNG needs. Unlike parsing, this keeps
+/// going after a bad CRC so every problem in the file is reported.
+pub fn validate_bytes(data: &[u8]) -> Vec<ValidationIssue> {
+    let signature = Png::STANDARD_HEADER.len();
+    if data.len() < signature || data[..signature] != Png::STANDARD_HEADER {
+        return vec![ValidationIssue {
+            offset: 0,
+            chunk_index: None,
+            problem: Problem::InvalidSignature,
+        }];
+    }
+
+    let mut issues = vec![];
+    let mut types: Vec<[u8; 4]> = vec![];
+    let mut offset = signature;
+    while offset < data.len() {
+        let index = types.len();
+        let issue = |offset, problem| ValidationIssue {
+            offset,
+            chunk_index: Some(index),
+            problem,
+        };
+        let Some(header) = data.get(offset..offset + 8) else {
+            issues.push(issue(offset, Problem::Truncated));
+            break;
+        };
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let chunk_type = [header[4], header[5], header[6], header[7]];
+        let data_end = offset + 8 + length;
+        let Some(crc_bytes) = data.get(data_end..data_end + 4) else {
+            issues.push(issue(offset, Problem::Truncated));
+            break;
+        };
+
+        if !chunk_type.iter().all(u8::is_ascii_alphabetic) {
+            issues.push(issue(
+                offset,
+                Problem::InvalidChunkType { bytes: chunk_type },
+            ));
+        }
+        let stored = u32::from_be_bytes([crc_bytes[0], crc_bytes[1], crc_bytes[2], crc_bytes[3]]);
+        let computed = CASTAGNOLI.checksum(&data[offset + 4..data_end]);
+        if stored != computed {
+            issues.push(issue(offset, Problem::CrcMismatch { stored, computed }));
+        }
+
+        types.push(chunk_type);
+        offset = data_end + 4;
+    }
+
+    if types.first() != Some(b"IHDR") {
+        issues.push(ValidationIssue {
+            offset: signature,
+            chunk_index: Some(0),
+            problem: Problem::MissingIhdr,
+        });
+    }
+    if !types.iter().any(|t| t == b"IDAT") {
+        issues.push(ValidationIssue {
+            offset: signature,
+            chunk_index: None,
+            problem: Problem::MissingIdat,
+        });
+    }
+    if types.last() != Some(b"IEND") {
+        issues.push(ValidationIssue {
+            offset,
+            chunk_index: types.len().checked_sub(1),
+            problem: Problem::MissingIend,
+        });
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.into())
+    }
+
+    fn valid_bytes() -> Vec<u8> {
+        Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("IDAT", "pixels"),
+            chunk("IEND", ""),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_valid() {
+        assert!(validate_bytes(&valid_bytes()).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let mut bytes = valid_bytes();
+        bytes[0] = 0;
+        let issues = validate_bytes(&bytes);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].problem, Problem::InvalidSignature);
+    }
+
+    #[test]
+    fn test_crc_mismatch() {
+        let mut bytes = valid_bytes();
+        // Last byte of the IDAT data: 8 signature + 18 IHDR + 8 IDAT header + 5
+        bytes[8 + 18 + 8 + 5] = b'X';
+        let issues = validate_bytes(&bytes);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].offset, 26);
+        assert_eq!(issues[0].chunk_index, Some(1));
+        assert!(matches!(issues[0].problem, Problem::CrcMismatch { .. }));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = valid_bytes();
+        let issues = validate_bytes(&bytes[..bytes.len() - 2]);
+        assert_eq!(issues[0].problem, Problem::Truncated);
+        assert_eq!(issues[0].chunk_index, Some(2));
+        assert_eq!(issues[1].problem, Problem::MissingIend);
+    }
+
+    #[test]
+    fn test_missing_chunks() {
+        let bytes = Png::from_chunks(vec![chunk("tEXt", "hello")]).as_bytes();
+        let problems: Vec<Problem> = validate_bytes(&bytes)
+            .into_iter()
+            .map(|issue| issue.problem)
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                Problem::MissingIhdr,
+                Problem::MissingIdat,
+                Problem::MissingIend
+            ]
+        );
+    }
+}